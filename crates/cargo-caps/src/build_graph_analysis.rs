@@ -10,7 +10,10 @@ pub struct DepKindSet {
 }
 
 /// How is the main target depending on a crate?
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
 pub enum DepKind {
     Unknown,
     Normal,
@@ -31,6 +34,48 @@ impl core::fmt::Display for DepKind {
     }
 }
 
+/// When does the code of an artifact run?
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// On the machines of the users of the workspace.
+    Runtime,
+
+    /// When building the workspace, e.g. a `build.rs` or a proc-macro.
+    BuildTime,
+
+    /// Only when testing the workspace.
+    Dev,
+}
+
+impl core::fmt::Display for Scope {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Runtime => write!(f, "runtime"),
+            Self::BuildTime => write!(f, "build-time"),
+            Self::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl DepKindSet {
+    /// The riskiest way the crate is used.
+    ///
+    /// If we don't know how it is used, we assume the worst.
+    pub fn scope(&self) -> Scope {
+        if self.kind.is_empty()
+            || self.kind.contains(&DepKind::Normal)
+            || self.kind.contains(&DepKind::Unknown)
+        {
+            Scope::Runtime
+        } else if self.kind.contains(&DepKind::Build) || self.kind.contains(&DepKind::ProcMacro) {
+            Scope::BuildTime
+        } else {
+            Scope::Dev
+        }
+    }
+}
+
 pub fn has_build_rs(package: &Package) -> bool {
     package
         .targets
@@ -49,7 +94,7 @@ struct Node {
 
 /// "Depends on".
 #[derive(Debug, Clone)]
-struct Edge {
+pub struct Edge {
     // how the dependent is using the dependee
     kind: BTreeSet<DepKind>,
 }
@@ -66,7 +111,7 @@ impl From<DepKind> for Edge {
 /// with edges pointing from dependent to dependee,
 /// so an edge means "depends on".
 #[derive(Default)]
-struct DepGraph {
+pub struct DepGraph {
     graph: petgraph::Graph<Node, Edge, Directed>,
     package_to_node: HashMap<PackageId, NodeIndex>,
 }
@@ -148,6 +193,19 @@ impl DepGraph {
         Ok(graph)
     }
 
+    /// All the edges of the graph, as `(dependent, dependency, how it is depended on)`.
+    pub fn dependencies(
+        &self,
+    ) -> impl Iterator<Item = (&PackageId, &PackageId, &BTreeSet<DepKind>)> {
+        self.graph.edge_references().map(|edge| {
+            (
+                &self.graph[edge.source()].id,
+                &self.graph[edge.target()].id,
+                &edge.weight().kind,
+            )
+        })
+    }
+
     fn analyze(mut self) -> HashMap<PackageId, DepKindSet> {
         self.compute_dependency_kinds();
 
@@ -279,6 +337,25 @@ mod tests {
         assert_eq!(&result[&pid("3rd")], &set(DepKind::Build));
     }
 
+    #[test]
+    fn test_scope() {
+        let kinds = |kinds: &[DepKind]| DepKindSet {
+            kind: kinds.iter().copied().collect(),
+        };
+        assert_eq!(kinds(&[]).scope(), Scope::Runtime);
+        assert_eq!(kinds(&[DepKind::Unknown]).scope(), Scope::Runtime);
+        assert_eq!(
+            kinds(&[DepKind::Build, DepKind::Normal]).scope(),
+            Scope::Runtime
+        );
+        assert_eq!(
+            kinds(&[DepKind::Build, DepKind::Dev]).scope(),
+            Scope::BuildTime
+        );
+        assert_eq!(kinds(&[DepKind::ProcMacro]).scope(), Scope::BuildTime);
+        assert_eq!(kinds(&[DepKind::Dev]).scope(), Scope::Dev);
+    }
+
     #[test]
     fn test_proc_macro() {
         let mut graph = DepGraph::default();
@@ -1,8 +1,12 @@
-use std::collections::BTreeSet;
+use std::sync::Arc;
 
+use cargo_metadata::camino::Utf8PathBuf;
 use serde::{Deserialize, Serialize};
 
-use crate::capability::CapabilitySet;
+use crate::{
+    capability::{CapabilitySet, SourceLocation},
+    config::WorkspaceConfig,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Pattern {
@@ -24,22 +28,79 @@ impl Pattern {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl core::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Exact(s) => write!(f, "{s}"),
+            Self::StartsWith(s) => write!(f, "{s}*"),
+        }
+    }
+}
+
+/// One of the patterns of a [`Rule`], and where it was written.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RulePattern {
+    pub pattern: Pattern,
+
+    /// Index of the rule in the file it was defined in.
+    pub rule_index: usize,
+
+    /// Where the pattern was defined, e.g. in `default_rules.eon`, if known.
+    pub location: Option<SourceLocation>,
+}
+
+impl core::fmt::Display for RulePattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Self {
+            pattern,
+            rule_index,
+            location,
+        } = self;
+        if let Some(location) = location {
+            write!(f, "`{pattern}` (rule #{rule_index} at {location})")
+        } else {
+            write!(f, "`{pattern}` (rule #{rule_index})")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Rule {
-    /// If the symbol matches this…
-    pub pattern: BTreeSet<Pattern>,
+    /// If the symbol matches any of these…
+    pub patterns: Vec<Arc<RulePattern>>,
 
     /// …then it is known to have these, and only these, capabitites
     pub caps: CapabilitySet,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The most specific rule matching a symbol.
+#[derive(Clone, Copy, Debug)]
+pub struct RuleMatch<'a> {
+    /// The capabilities of the rule.
+    pub caps: &'a CapabilitySet,
+
+    /// The pattern that matched.
+    pub pattern: &'a Arc<RulePattern>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct SymbolRules {
     /// Most specific match wins! So if `foo::bar` matches, then `foo` is ignored.
+    ///
+    /// If several rules are equally specific, the first one wins.
     pub rules: Vec<Rule>,
 }
 
 impl SymbolRules {
+    /// The `symbols` rules of the workspace config, followed by the default rules.
+    ///
+    /// So the rules of the config win over equally specific default rules.
+    pub fn load(config: &WorkspaceConfig) -> Self {
+        let mut rules = config.symbol_rules.clone();
+        rules.rules.extend(Self::load_default().rules);
+        rules
+    }
+
     pub fn load_default() -> Self {
         static DEFAULT_RULES_EON: &str = include_str!("default_rules.eon");
 
@@ -50,66 +111,139 @@ impl SymbolRules {
 
         let loaded: DefaultRules =
             eon::from_str(DEFAULT_RULES_EON).expect("Failed to parse default_rules.eon");
-        Self {
-            rules: loaded.rules.into_iter().map(|rule| rule.into()).collect(),
-        }
+        Self::from_serialized(
+            &loaded.rules,
+            &Utf8PathBuf::from("default_rules.eon").into(),
+            DEFAULT_RULES_EON,
+            "rules",
+        )
     }
-}
 
-#[derive(Debug, Clone, Deserialize)]
-struct SerializedRule {
-    /// Capabilities for this rule
-    caps: CapabilitySet,
+    /// Rules that were loaded from the `key` list of `source`, which was read from `path`.
+    ///
+    /// `source` is used to find the line number of each pattern.
+    pub fn from_serialized(
+        serialized: &[SerializedRule],
+        path: &Arc<Utf8PathBuf>,
+        source: &str,
+        key: &str,
+    ) -> Self {
+        let line_nrs = pattern_line_nrs(source, key);
 
-    /// String patterns that will be converted to Match using `Match::from_str`
-    patterns: BTreeSet<String>,
+        let rules = serialized
+            .iter()
+            .enumerate()
+            .map(|(rule_index, rule)| Rule {
+                patterns: rule
+                    .patterns
+                    .iter()
+                    .enumerate()
+                    .map(|(pattern_index, pattern)| {
+                        let line_nr = line_nrs
+                            .get(rule_index)
+                            .and_then(|line_nrs| line_nrs.get(pattern_index));
+                        Arc::new(RulePattern {
+                            pattern: Pattern::parse_simple(pattern),
+                            rule_index,
+                            location: line_nr.map(|&line_nr| SourceLocation {
+                                path: Arc::clone(path),
+                                line_nr,
+                            }),
+                        })
+                    })
+                    .collect(),
+                caps: rule.caps.clone(),
+            })
+            .collect();
+
+        Self { rules }
+    }
 }
 
-impl From<SerializedRule> for Rule {
-    fn from(rule: SerializedRule) -> Self {
-        Self {
-            caps: rule.caps,
-            pattern: rule
-                .patterns
-                .into_iter()
-                .map(|s| Pattern::parse_simple(&s))
-                .collect(),
+/// The line number of each pattern of each rule in the `key` list of `source`.
+///
+/// Indexed by rule, then by pattern. Empty if the list could not be found.
+fn pattern_line_nrs(source: &str, key: &str) -> Vec<Vec<usize>> {
+    use eon::external::eon_syntax::{TokenTree, TokenValue, unescape_and_unquote};
+
+    fn get<'a, 's>(tree: &'a TokenTree<'s>, key: &str) -> Option<&'a TokenTree<'s>> {
+        let TokenValue::Map(map) = &tree.value else {
+            return None;
+        };
+        map.key_values
+            .iter()
+            .find(|key_value| match &key_value.key.value {
+                TokenValue::Identifier(ident) => ident == key,
+                TokenValue::QuotedString(quoted) => {
+                    unescape_and_unquote(quoted).is_ok_and(|string| string == key)
+                }
+                _ => false,
+            })
+            .map(|key_value| &key_value.value)
+    }
+
+    fn values<'a, 's>(tree: Option<&'a TokenTree<'s>>) -> &'a [TokenTree<'s>] {
+        match tree.map(|tree| &tree.value) {
+            Some(TokenValue::List(list)) => &list.values,
+            _ => &[],
         }
     }
+
+    let Ok(tree) = TokenTree::parse_str(source) else {
+        return vec![];
+    };
+    let line_nr =
+        |span: eon::external::eon_syntax::Span| 1 + source[..span.start].matches('\n').count();
+
+    values(get(&tree, key))
+        .iter()
+        .map(|rule| {
+            values(get(rule, "patterns"))
+                .iter()
+                .filter_map(|pattern| pattern.span.map(line_nr))
+                .collect()
+        })
+        .collect()
+}
+
+/// A symbol rule, as written in `default_rules.eon` or in the `symbols` of the workspace config.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SerializedRule {
+    /// Capabilities for this rule
+    pub caps: CapabilitySet,
+
+    /// String patterns that will be converted to [`Pattern`] using [`Pattern::parse_simple`].
+    ///
+    /// In the same order as in the file.
+    pub patterns: Vec<String>,
 }
 
 impl SymbolRules {
     /// Find the most specific matching rule for a symbol
-    pub fn match_symbol(&self, symbol: &str) -> Option<&CapabilitySet> {
-        let mut best_match: Option<(&Rule, usize)> = None;
+    pub fn match_symbol(&self, symbol: &str) -> Option<RuleMatch<'_>> {
+        let mut best_match: Option<(RuleMatch<'_>, usize)> = None;
 
         for rule in &self.rules {
-            for m in &rule.pattern {
-                match m {
-                    Pattern::Exact(pattern) if pattern == symbol => {
-                        let specificity = pattern.len();
-                        if best_match
-                            .as_ref()
-                            .is_none_or(|(_, prev_spec)| specificity > *prev_spec)
-                        {
-                            best_match = Some((rule, specificity));
-                        }
-                    }
-                    Pattern::StartsWith(pattern) if symbol.starts_with(pattern) => {
-                        let specificity = pattern.len();
-                        if best_match
-                            .as_ref()
-                            .is_none_or(|(_, prev_spec)| specificity > *prev_spec)
-                        {
-                            best_match = Some((rule, specificity));
-                        }
-                    }
-                    _ => {}
+            for rule_pattern in &rule.patterns {
+                let (is_match, specificity) = match &rule_pattern.pattern {
+                    Pattern::Exact(pattern) => (pattern == symbol, pattern.len()),
+                    Pattern::StartsWith(pattern) => (symbol.starts_with(pattern), pattern.len()),
+                };
+                if is_match
+                    && best_match
+                        .as_ref()
+                        .is_none_or(|(_, prev_spec)| specificity > *prev_spec)
+                {
+                    let rule_match = RuleMatch {
+                        caps: &rule.caps,
+                        pattern: rule_pattern,
+                    };
+                    best_match = Some((rule_match, specificity));
                 }
             }
         }
 
-        best_match.map(|(rule, _)| &rule.caps)
+        best_match.map(|(rule_match, _)| rule_match)
     }
 }
 
@@ -117,9 +251,182 @@ impl SymbolRules {
 fn test_default_rules() {
     use crate::Capability;
     let rules = SymbolRules::load_default();
-    assert_eq!(rules.match_symbol("unknown"), None);
+    assert!(rules.match_symbol("unknown").is_none());
+
+    let malloc = rules.match_symbol("malloc").unwrap();
+    assert_eq!(malloc.caps, &core::iter::once(Capability::Alloc).collect());
+    assert_eq!(malloc.pattern.pattern, Pattern::Exact("malloc".to_owned()));
+
+    let location = malloc.pattern.location.as_ref().unwrap();
+    assert_eq!(location.path.as_str(), "default_rules.eon");
+    let line = include_str!("default_rules.eon")
+        .lines()
+        .nth(location.line_nr - 1)
+        .unwrap();
+    assert_eq!(line.trim(), r#""malloc""#);
+
+    let caps_of = |symbol: &str| {
+        rules
+            .match_symbol(symbol)
+            .unwrap()
+            .caps
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        caps_of("std::fs::read_to_string"),
+        ["alloc", "panic", "fs.read"]
+    );
+    assert_eq!(
+        caps_of("std::path::Path::exists"),
+        ["alloc", "panic", "fs.read"]
+    );
+    assert_eq!(
+        caps_of("std::fs::write::inner"),
+        ["alloc", "panic", "fs.write"]
+    );
+    assert_eq!(
+        caps_of("std::fs::remove_file"),
+        ["alloc", "panic", "fs.write"]
+    );
+    assert_eq!(
+        caps_of("std::fs::OpenOptions::open"),
+        ["alloc", "panic", "fs"],
+        "OpenOptions can both read and write"
+    );
+    assert_eq!(
+        caps_of("std::fs::OpenOptions::_open"),
+        ["alloc", "panic", "fs"],
+        "Both `File::open` and `File::create` are inlined to this"
+    );
+    assert_eq!(
+        caps_of("std::fs::OpenOptions::append"),
+        ["alloc", "panic", "fs.write"]
+    );
+    assert_eq!(
+        caps_of("std::sys::fs::metadata"),
+        ["alloc", "panic", "fs.read"]
+    );
+    assert_eq!(
+        caps_of("std::sys::fs::remove_file"),
+        ["alloc", "panic", "fs.write"]
+    );
+    assert_eq!(
+        caps_of("std::sys::fs::unix::File::open_c"),
+        ["alloc", "panic", "fs"]
+    );
+    assert_eq!(caps_of("stat64"), ["fs.read"]);
+    assert_eq!(caps_of("unlink"), ["fs.write"]);
+    assert_eq!(caps_of("<[u8]>::iter"), ["alloc"]);
+    assert_eq!(caps_of("<*const ()>::is_aligned_to"), ["alloc"]);
+
+    assert_eq!(
+        caps_of("std::net::tcp::TcpListener::bind"),
+        ["alloc", "panic", "net.listen"]
+    );
+    assert_eq!(
+        caps_of("std::sys::net::connection::socket::TcpListener::bind::inner"),
+        ["alloc", "panic", "net.listen"],
+        "`TcpListener::bind` is inlined to this"
+    );
+    assert_eq!(
+        caps_of("std::sys::net::connection::socket::UdpSocket::bind"),
+        ["alloc", "panic", "net.listen"]
+    );
+    assert_eq!(
+        caps_of("std::sys::net::connection::socket::TcpStream::connect"),
+        ["alloc", "panic", "net.connect"]
+    );
+    assert_eq!(
+        caps_of("std::sys::net::connection::socket::TcpStream::peer_addr"),
+        ["alloc", "panic", "net"]
+    );
+    assert_eq!(
+        caps_of("std::net::TcpStream::connect_timeout"),
+        ["alloc", "panic", "net.connect"]
+    );
+    assert_eq!(
+        caps_of("std::net::socket_addr::resolve_socket_addr"),
+        ["alloc", "panic", "net.dns"]
+    );
+    assert_eq!(
+        caps_of("std::net::TcpStream::peer_addr"),
+        ["alloc", "panic", "net"]
+    );
+    assert_eq!(caps_of("getaddrinfo"), ["net.dns"]);
+    assert_eq!(caps_of("listen"), ["net.listen"]);
+    assert_eq!(caps_of("connect"), ["net.connect"]);
+}
+
+#[test]
+fn test_config_rules() {
+    use crate::Capability;
+
+    let source = r#"
+rules: []
+symbols: [
+    {
+        caps: ["net"]
+        patterns: ["acme_rpc_*"]
+    }
+    {
+        caps: ["fs"]
+        patterns: ["malloc"]
+    }
+]
+"#;
+    let mut config: WorkspaceConfig = eon::from_str(source).unwrap();
+    config.symbol_rules = SymbolRules::from_serialized(
+        &config.symbols,
+        &Arc::new(Utf8PathBuf::from("cargo-caps.eon")),
+        source,
+        "symbols",
+    );
+    let rules = SymbolRules::load(&config);
+
+    let rpc = rules.match_symbol("acme_rpc_connect").unwrap();
+    assert_eq!(rpc.caps, &core::iter::once(Capability::Net).collect());
+    assert_eq!(
+        rpc.pattern.location.as_ref().unwrap().to_string(),
+        "cargo-caps.eon:6"
+    );
+
+    let malloc = rules.match_symbol("malloc").unwrap();
+    assert_eq!(
+        malloc.caps,
+        &core::iter::once(Capability::FS).collect(),
+        "Config rules should win over equally specific default rules"
+    );
+
+    let free = rules.match_symbol("free").unwrap();
+    assert_eq!(
+        free.pattern.location.as_ref().unwrap().path.as_str(),
+        "default_rules.eon"
+    );
+}
+
+#[test]
+fn test_pattern_line_nrs() {
+    let source = r#"
+// "acme_*" is mentioned in this comment
+symbols: [
+    {
+        caps: ["net"]
+        patterns: ['acme_*', "acme_rpc"]
+    }
+    {
+        caps: ["fs"]
+        patterns: [
+            "acme_*"
+        ]
+    }
+]
+"#;
     assert_eq!(
-        rules.match_symbol("malloc"),
-        Some(&core::iter::once(Capability::Alloc).collect())
+        pattern_line_nrs(source, "symbols"),
+        vec![vec![6, 6], vec![11]]
     );
+    assert!(pattern_line_nrs(source, "rules").is_empty());
+    assert!(pattern_line_nrs("[package.metadata.caps]", "symbols").is_empty());
 }
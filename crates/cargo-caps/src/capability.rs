@@ -9,8 +9,11 @@ use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    CrateName, Symbol, cap_rule::SymbolRules, reservoir_sample::ReservoirSampleExt as _,
-    rust_path::RustPath, symbol::FunctionOrPath,
+    CrateName, Symbol,
+    cap_rule::{RulePattern, SymbolRules},
+    reservoir_sample::ReservoirSampleExt as _,
+    rust_path::RustPath,
+    symbol::FunctionOrPath,
 };
 
 /// A set of capabilities.
@@ -27,6 +30,10 @@ impl CapabilitySet {
         self.0.insert(cap)
     }
 
+    pub fn remove(&mut self, cap: &Capability) -> bool {
+        self.0.remove(cap)
+    }
+
     pub fn contains(&self, cap: &Capability) -> bool {
         self.0.contains(cap)
     }
@@ -39,6 +46,18 @@ impl CapabilitySet {
         self.0.iter()
     }
 
+    /// Does this set of granted capabilities cover `cap`?
+    ///
+    /// An umbrella like `fs` covers its parts (`fs.read` and `fs.write`),
+    /// and all the parts together cover the umbrella.
+    pub fn covers(&self, cap: Capability) -> bool {
+        self.contains(&cap)
+            || cap
+                .umbrella()
+                .is_some_and(|umbrella| self.contains(&umbrella))
+            || (!cap.parts().is_empty() && cap.parts().iter().all(|part| self.contains(part)))
+    }
+
     pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a Capability> {
         self.0.difference(&other.0)
     }
@@ -100,20 +119,56 @@ pub enum Capability {
     #[serde(rename = "thread")]
     Thread,
 
-    /// Connect over the network and/or listen for incoming network traffic
+    /// Use the network in a way we can't tell is only connecting, listening or resolving names.
+    ///
+    /// Also an umbrella: granting `net` grants [`Self::NetConnect`], [`Self::NetListen`] and [`Self::NetDns`].
     #[serde(rename = "net")]
     Net,
 
-    /// Open a file on disk for reading or writing
+    /// Open outbound network connections
+    #[serde(rename = "net.connect")]
+    NetConnect,
+
+    /// Listen for, and accept, incoming network connections
+    #[serde(rename = "net.listen")]
+    NetListen,
+
+    /// Resolve host names, e.g. with DNS
+    #[serde(rename = "net.dns")]
+    NetDns,
+
+    /// Access files on disk in a way we can't tell is only reading or writing.
+    ///
+    /// Also an umbrella: granting `fs` grants both [`Self::FsRead`] and [`Self::FsWrite`].
     #[serde(rename = "fs")]
     FS,
 
+    /// Read files, directories and file metadata on disk
+    #[serde(rename = "fs.read")]
+    FsRead,
+
+    /// Create, write, rename or remove files and directories on disk
+    #[serde(rename = "fs.write")]
+    FsWrite,
+
+    /// Calls foreign (e.g. C) functions, or links a native library.
+    ///
+    /// NOT contagious, like [`Self::BuildRs`]:
+    /// what the foreign code can do shows up as the symbols it uses.
+    #[serde(rename = "ffi")]
+    Ffi,
+
     // -------------------------------
     // Dangerous ones:
     /// Contains unsafe code blocks or functions
     #[serde(rename = "unsafe")]
     Unsafe,
 
+    /// Contains inline assembly (`asm!`, `global_asm!` or `naked_asm!`),
+    /// which can make system calls directly, bypassing all our symbol rules.
+    #[serde(rename = "asm")]
+    Asm,
+
     /// May call any CLI command
     #[serde(rename = "command")]
     Command,
@@ -139,9 +194,15 @@ impl Capability {
             | Self::Stdio
             | Self::Thread
             | Self::Net
-            | Self::FS => false,
-
-            Self::Unsafe | Self::Command | Self::Unknown | Self::Wildcard => true,
+            | Self::NetConnect
+            | Self::NetListen
+            | Self::NetDns
+            | Self::FS
+            | Self::FsRead
+            | Self::FsWrite
+            | Self::Ffi => false,
+
+            Self::Unsafe | Self::Asm | Self::Command | Self::Unknown | Self::Wildcard => true,
         }
     }
 
@@ -149,7 +210,61 @@ impl Capability {
     pub fn inherit_from_dependency(&self) -> bool {
         // Motivation: all critical dependencies need explicit vetting,
         // and once vetted they _should_ shrink from e.g. "unknown" or "unsafe" to something actually known.
-        !self.is_critical()
+        !self.is_critical() && *self != Self::Ffi
+    }
+
+    /// The capability this is a part of, e.g. `fs` for `fs.read`.
+    pub fn umbrella(&self) -> Option<Self> {
+        match self {
+            Self::NetConnect | Self::NetListen | Self::NetDns => Some(Self::Net),
+            Self::FsRead | Self::FsWrite => Some(Self::FS),
+            _ => None,
+        }
+    }
+
+    /// The parts of an umbrella capability, e.g. `fs.read` and `fs.write` for `fs`.
+    pub fn parts(&self) -> &'static [Self] {
+        match self {
+            Self::Net => &[Self::NetConnect, Self::NetListen, Self::NetDns],
+            Self::FS => &[Self::FsRead, Self::FsWrite],
+            _ => &[],
+        }
+    }
+
+    /// Could having `self` mean having `other`?
+    ///
+    /// True if they are the same, or if one is an umbrella of the other:
+    /// `fs` could be a write, so it overlaps `fs.write`.
+    pub fn overlaps(&self, other: Self) -> bool {
+        *self == other || self.umbrella() == Some(other) || other.umbrella() == Some(*self)
+    }
+}
+
+impl Capability {
+    /// A one-line description of the capability.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::BuildRs => "Has a custom build step (build.rs)",
+            Self::Alloc => "Allocate memory",
+            Self::Panic => "Call panic!",
+            Self::Time => "Read the current time and/or date",
+            Self::Sysinfo => "Read environment variables, process info, …",
+            Self::Stdio => "Read and write to stdin, stdout, stderr",
+            Self::Thread => "Spawn threads",
+            Self::Net => "Use the network: connect, listen and/or resolve host names",
+            Self::NetConnect => "Open outbound network connections",
+            Self::NetListen => "Listen for, and accept, incoming network connections",
+            Self::NetDns => "Resolve host names, e.g. with DNS",
+            Self::FS => "Access files on disk, for reading and/or writing",
+            Self::FsRead => "Read files, directories and file metadata on disk",
+            Self::FsWrite => "Create, write, rename or remove files and directories on disk",
+            Self::Ffi => "Call foreign functions, or link a native library",
+            Self::Unsafe => "Contains unsafe code blocks or functions",
+            Self::Asm => "Contains inline assembly, which can make system calls directly",
+            Self::Command => "May call any CLI command",
+            Self::Unknown => "We don't know what it can do",
+            Self::Wildcard => "Anything",
+        }
     }
 }
 
@@ -164,8 +279,15 @@ impl core::fmt::Display for Capability {
             Self::Stdio => write!(f, "stdio"),
             Self::Thread => write!(f, "thread"),
             Self::Net => write!(f, "net"),
+            Self::NetConnect => write!(f, "net.connect"),
+            Self::NetListen => write!(f, "net.listen"),
+            Self::NetDns => write!(f, "net.dns"),
             Self::FS => write!(f, "fs"),
+            Self::FsRead => write!(f, "fs.read"),
+            Self::FsWrite => write!(f, "fs.write"),
+            Self::Ffi => write!(f, "ffi"),
             Self::Unsafe => write!(f, "unsafe"),
+            Self::Asm => write!(f, "asm"),
             Self::Command => write!(f, "command"),
             Self::Unknown => write!(f, "unknown"),
             Self::Wildcard => write!(f, "*"),
@@ -173,6 +295,16 @@ impl core::fmt::Display for Capability {
     }
 }
 
+impl core::str::FromStr for Capability {
+    type Err = serde::de::value::Error;
+
+    /// Parse the same names as used in the config, e.g. `"fs"` or `"build.rs"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use serde::de::IntoDeserializer as _;
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
 impl Capability {
     pub fn emoji(&self) -> &'static str {
         match self {
@@ -184,8 +316,15 @@ impl Capability {
             Self::Stdio => "📝",
             Self::Thread => "🧵",
             Self::Net => "🌐",
+            Self::NetConnect => "📡",
+            Self::NetListen => "👂",
+            Self::NetDns => "🔎",
             Self::FS => "📁",
+            Self::FsRead => "📖",
+            Self::FsWrite => "💾",
+            Self::Ffi => "🔗",
             Self::Unsafe => "☢️",
+            Self::Asm => "🔩",
             Self::Command => "⚠️ ",
             Self::Unknown => "❓",
             Self::Wildcard => "🃏 ", // TODO: its own symbol?
@@ -209,11 +348,17 @@ pub type Reasons = BTreeSet<Reason>;
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Reason {
-    /// This path matches a rule. TODO: which rule? Where?
-    PathMatchedRule(RustPath),
-
-    /// This symbol matches a rule. TODO: which rule? Where?
-    SymbolMatchedRule(Symbol),
+    /// This path matches a rule.
+    PathMatchedRule {
+        path: RustPath,
+        rule: Arc<RulePattern>,
+    },
+
+    /// This symbol matches a rule.
+    SymbolMatchedRule {
+        symbol: Symbol,
+        rule: Arc<RulePattern>,
+    },
 
     /// The reason we have this high capability is because we didn't succeed in understanding the source code.
     SourceParseError(String),
@@ -232,18 +377,24 @@ pub enum Reason {
 
     /// We have this capability because we depend on this crate, which has that capability.
     Crate(CrateName),
+
+    /// The build script links this native library, with `cargo:rustc-link-lib`.
+    LinkedLib(String),
 }
 
 impl core::fmt::Display for Reason {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Self::PathMatchedRule(path) | Self::UmatchedStandardPath(path) => path.fmt(f),
-            Self::SourceParseError(err) => write!(f, "{err:#?}"),
-            Self::SymbolMatchedRule(symbol) | Self::UnmatchedSymbol(symbol) => {
-                write!(f, "{}", symbol.format(false))
+            Self::PathMatchedRule { path, rule } => write!(f, "{path} (matched {rule})"),
+            Self::SymbolMatchedRule { symbol, rule } => {
+                write!(f, "{} (matched {rule})", symbol.format(false))
             }
+            Self::UmatchedStandardPath(path) => path.fmt(f),
+            Self::SourceParseError(err) => write!(f, "{err:#?}"),
+            Self::UnmatchedSymbol(symbol) => write!(f, "{}", symbol.format(false)),
             Self::SourceCodeAnalysis { location } => write!(f, "source: {location}"),
             Self::Crate(crate_name) => crate_name.fmt(f),
+            Self::LinkedLib(lib) => write!(f, "links the native library {lib}"),
         }
     }
 }
@@ -281,12 +432,14 @@ impl DeducedCaps {
                     let fun_name = fun_name.trim_start_matches('_');
 
                     // Check rules for the symbol
-                    if let Some(capabilities) = rules.match_symbol(fun_name) {
-                        for &capability in capabilities {
-                            self.caps
-                                .entry(capability)
-                                .or_default()
-                                .insert(Reason::SymbolMatchedRule(symbol.clone()));
+                    if let Some(rule_match) = rules.match_symbol(fun_name) {
+                        for &capability in rule_match.caps {
+                            self.caps.entry(capability).or_default().insert(
+                                Reason::SymbolMatchedRule {
+                                    symbol: symbol.clone(),
+                                    rule: Arc::clone(rule_match.pattern),
+                                },
+                            );
                         }
                     } else {
                         self.caps
@@ -299,12 +452,14 @@ impl DeducedCaps {
                 FunctionOrPath::RustPath(rust_path) => {
                     let path_str = rust_path.to_string();
                     // Check rules for the path
-                    if let Some(capabilities) = rules.match_symbol(&path_str) {
-                        for &capability in capabilities {
-                            self.caps
-                                .entry(capability)
-                                .or_default()
-                                .insert(Reason::PathMatchedRule(rust_path.clone()));
+                    if let Some(rule_match) = rules.match_symbol(&path_str) {
+                        for &capability in rule_match.caps {
+                            self.caps.entry(capability).or_default().insert(
+                                Reason::PathMatchedRule {
+                                    path: rust_path.clone(),
+                                    rule: Arc::clone(rule_match.pattern),
+                                },
+                            );
                         }
                     } else {
                         // No rule matched
@@ -337,12 +492,15 @@ impl DeducedCaps {
     pub fn add_path(&mut self, rules: &SymbolRules, rust_path: RustPath) -> anyhow::Result<()> {
         let path_str = rust_path.to_string();
         // Check rules for the path
-        if let Some(capabilities) = rules.match_symbol(&path_str) {
-            for &capability in capabilities {
+        if let Some(rule_match) = rules.match_symbol(&path_str) {
+            for &capability in rule_match.caps {
                 self.caps
                     .entry(capability)
                     .or_default()
-                    .insert(Reason::PathMatchedRule(rust_path.clone()));
+                    .insert(Reason::PathMatchedRule {
+                        path: rust_path.clone(),
+                        rule: Arc::clone(rule_match.pattern),
+                    });
             }
         } else {
             // No rule matched - assume an external crate:
@@ -379,12 +537,14 @@ impl DeducedCaps {
 
 pub fn format_reasons(reasons: &Reasons) -> String {
     let mut crates = vec![];
+    let mut matched_rules = BTreeSet::new();
     let mut path_matched_rules = vec![];
     let mut symbol_matched_rules = vec![];
     let mut unmatched_paths = vec![];
     let mut unmatched_symbols = vec![];
     let mut source_parse_errors = vec![];
     let mut source_code_locations = vec![];
+    let mut linked_libs = vec![];
 
     for reason in reasons {
         match reason {
@@ -397,11 +557,13 @@ pub fn format_reasons(reasons: &Reasons) -> String {
             Reason::UnmatchedSymbol(symbol) => {
                 unmatched_symbols.push(symbol);
             }
-            Reason::PathMatchedRule(rust_path) => {
-                path_matched_rules.push(rust_path);
+            Reason::PathMatchedRule { path, rule } => {
+                path_matched_rules.push(format!("{path} ({})", rule.pattern));
+                matched_rules.insert(rule);
             }
-            Reason::SymbolMatchedRule(symbol) => {
-                symbol_matched_rules.push(symbol);
+            Reason::SymbolMatchedRule { symbol, rule } => {
+                symbol_matched_rules.push(format!("{} ({})", symbol.demangled, rule.pattern));
+                matched_rules.insert(rule);
             }
             Reason::SourceParseError(error) => {
                 source_parse_errors.push(error);
@@ -409,6 +571,9 @@ pub fn format_reasons(reasons: &Reasons) -> String {
             Reason::SourceCodeAnalysis { location } => {
                 source_code_locations.push(location);
             }
+            Reason::LinkedLib(lib) => {
+                linked_libs.push(lib);
+            }
         }
     }
 
@@ -430,16 +595,24 @@ pub fn format_reasons(reasons: &Reasons) -> String {
         string
     }
 
+    let with_rule_locations = |string: String| {
+        let locations = matched_rules
+            .iter()
+            .filter_map(|rule| rule.location.as_ref())
+            .collect_vec();
+        if locations.is_empty() {
+            string
+        } else {
+            format!("{string} [{}]", format_long_list("defined at", &locations))
+        }
+    };
+
     if !crates.is_empty() {
         format_long_list("dependencies", &crates)
     } else if !path_matched_rules.is_empty() {
-        format_long_list("rule for", &path_matched_rules)
+        with_rule_locations(format_long_list("rule for", &path_matched_rules))
     } else if !symbol_matched_rules.is_empty() {
-        let symbol_matched_rules = symbol_matched_rules
-            .into_iter()
-            .map(|s| &s.demangled)
-            .collect_vec();
-        format_long_list("rule for", &symbol_matched_rules)
+        with_rule_locations(format_long_list("rule for", &symbol_matched_rules))
     } else if !unmatched_paths.is_empty() {
         format_long_list("unknown paths", &unmatched_paths)
     } else if !unmatched_symbols.is_empty() {
@@ -452,6 +625,8 @@ pub fn format_reasons(reasons: &Reasons) -> String {
         format_long_list("source parse error", &source_parse_errors)
     } else if !source_code_locations.is_empty() {
         format_long_list("source code", &source_code_locations)
+    } else if !linked_libs.is_empty() {
+        format_long_list("linked libraries", &linked_libs)
     } else {
         unreachable!()
     }
@@ -2,15 +2,17 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::{
     CrateName,
-    build_graph_analysis::{DepKind, DepKindSet, has_build_rs},
+    build_graph_analysis::{DepKind, DepKindSet, Scope, has_build_rs},
     cap_rule::SymbolRules,
     capability::{Capability, CapabilitySet, DeducedCaps, Reason, format_reasons},
-    config::WorkspaceConfig,
+    config::{CrateTarget, Denial, WorkspaceConfig},
     src_analysis::ParsedRust,
 };
 use anyhow::Context as _;
 use cargo_metadata::{
-    Artifact, DependencyKind, Metadata, Package, PackageId, TargetKind, camino::Utf8Path,
+    Artifact, DependencyKind, Metadata, Package, PackageId, TargetKind,
+    camino::{Utf8Path, Utf8PathBuf},
+    semver::Version,
 };
 use itertools::Itertools as _;
 
@@ -18,7 +20,112 @@ use itertools::Itertools as _;
 #[derive(Default)]
 pub struct CheckerOutput {
     pub crate_caps: HashMap<CrateName, BTreeMap<TargetKind, DeducedCaps>>,
+
+    /// The verdict of every artifact we checked, in the order they were built.
+    pub checked: Vec<CheckedArtifact>,
+
     pub num_artifacts_passed: usize,
+
+    /// The native libraries each package links, according to the output of its build script.
+    pub linked_libs: HashMap<PackageId, Vec<String>>,
+}
+
+/// The result of checking one artifact of a package, e.g. its library or its `build.rs`.
+pub struct CheckedArtifact {
+    pub package_id: PackageId,
+    pub crate_name: CrateName,
+    pub version: Version,
+
+    /// Path to the `Cargo.toml` of the package.
+    pub manifest_path: Utf8PathBuf,
+
+    pub target_kind: TargetKind,
+
+    /// How the workspace depends on this package.
+    pub dep_kinds: DepKindSet,
+
+    /// The enabled features of the package.
+    pub features: Vec<String>,
+
+    /// What capabilities we think it has.
+    pub caps: DeducedCaps,
+
+    /// What capabilities it was granted by the [`WorkspaceConfig`].
+    pub allowed: CapabilitySet,
+
+    /// The capabilities it has, but wasn't granted (or was denied).
+    pub violations: CapabilitySet,
+
+    /// The capabilities it has that were forbidden by a deny rule, and by which.
+    pub denials: BTreeMap<Capability, Vec<Denial>>,
+
+    /// The capabilities it has beyond what the crate declares in its own `[package.metadata.caps]`.
+    pub undeclared: CapabilitySet,
+}
+
+impl CheckedArtifact {
+    /// For looking up the grants of this artifact.
+    pub fn crate_target(&self) -> CrateTarget<'_> {
+        CrateTarget {
+            package_id: &self.package_id,
+            name: &self.crate_name,
+            version: &self.version,
+            kind: &self.target_kind,
+            dep_kinds: &self.dep_kinds,
+            features: &self.features,
+        }
+    }
+
+    /// When does the code of this artifact run?
+    ///
+    /// `build.rs` files and proc-macros always run at build time,
+    /// even for runtime dependencies.
+    pub fn scope(&self) -> Scope {
+        match self.target_kind {
+            TargetKind::CustomBuild | TargetKind::ProcMacro => Scope::BuildTime,
+            _ => self.dep_kinds.scope(),
+        }
+    }
+}
+
+impl CheckerOutput {
+    /// Several files can belong to the same artifact, so we merge them.
+    fn add_checked(&mut self, checked: CheckedArtifact) {
+        if let Some(existing) = self.checked.iter_mut().find(|existing| {
+            existing.package_id == checked.package_id && existing.target_kind == checked.target_kind
+        }) {
+            existing.caps.extend(checked.caps);
+            existing
+                .violations
+                .extend(checked.violations.iter().copied());
+            existing
+                .undeclared
+                .extend(checked.undeclared.iter().copied());
+            for (cap, denials) in checked.denials {
+                let existing_denials = existing.denials.entry(cap).or_default();
+                for denial in denials {
+                    if !existing_denials.contains(&denial) {
+                        existing_denials.push(denial);
+                    }
+                }
+            }
+        } else {
+            self.checked.push(checked);
+        }
+    }
+}
+
+/// How much [`Checker`] prints about each crate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Verbosity {
+    /// Print details about each crate.
+    pub verbose: bool,
+
+    /// Show crates with no capabilities after filtering.
+    pub show_empty: bool,
+
+    /// Don't print anything about each crate, just compute [`CheckerOutput`].
+    pub quiet: bool,
 }
 
 pub struct Checker {
@@ -26,7 +133,7 @@ pub struct Checker {
     pub rules: SymbolRules,
     pub config: WorkspaceConfig,
     pub metadata: Metadata,
-    pub show_empty: bool,
+    pub verbosity: Verbosity,
 }
 
 impl Checker {
@@ -34,7 +141,6 @@ impl Checker {
         &self,
         output: &mut CheckerOutput,
         crate_infos: &HashMap<PackageId, DepKindSet>,
-        verbose: bool,
         artifact: &cargo_metadata::Artifact,
     ) -> anyhow::Result<()> {
         if artifact.executable.is_some() {
@@ -54,13 +160,9 @@ impl Checker {
         let Some(set) = crate_infos.get(&artifact.package_id) else {
             // Not sure why we sometimes end up here.
             // Examples: bitflags block2 objc2 objc2_app_kit memoffset rustix
-            println!("ERROR: unknown crate {}", artifact.target.name);
+            eprintln!("ERROR: unknown crate {}", artifact.target.name);
             return Ok(());
         };
-        // TODO
-        // if !set.kind.contains(&DepKind::Normal) {
-        //     return Ok(()); // ignore build dependencies, proc-macros etc - they cannot affect users machines
-        // }
 
         for file_path in &artifact.filenames {
             if std::path::Path::new(file_path.as_str())
@@ -72,9 +174,8 @@ impl Checker {
                 // It what makes `cargo check` faster than `cargo build`.
                 // But we cannot parse these files, so we just ignore them
             } else {
-                let did_print =
-                    self.add_artifact(output, package, artifact, file_path, set, verbose)?;
-                if !did_print {
+                let passed = self.add_artifact(output, package, artifact, file_path, set)?;
+                if passed {
                     output.num_artifacts_passed += 1;
                 }
             }
@@ -137,6 +238,16 @@ impl Checker {
             }
         }
 
+        if artifact_kind != &TargetKind::CustomBuild
+            && let Some(linked_libs) = output.linked_libs.get(&package.id)
+        {
+            deduced_caps
+                .caps
+                .entry(Capability::Ffi)
+                .or_default()
+                .extend(linked_libs.iter().cloned().map(Reason::LinkedLib));
+        }
+
         // Extend capabilities with the capabilities of our actual dependencies.
         // TODO: we do it again below, but differently
         for (dep_crate_name, _) in core::mem::take(&mut deduced_caps.unresolved_crates) {
@@ -157,7 +268,7 @@ impl Checker {
                     }
                 } else {
                     // TODO: return error?
-                    println!(
+                    eprintln!(
                         "{crate_name} depends on '{dep_crate_name}' (according to cargo-caps), but we have no Lib capabilities stored for it, only {:?}",
                         crate_caps.keys()
                     );
@@ -201,14 +312,14 @@ impl Checker {
                         }
                     } else {
                         // TODO: return error?
-                        println!(
+                        eprintln!(
                             "{crate_name} depends on '{dep_crate_name}' (according to cargo-metadata), but we have no Lib capabilities stored for it, only {:?}",
                             crate_caps.keys()
                         );
                     }
                 } else {
                     // TODO: figure out why we sometimes end up here
-                    println!(
+                    eprintln!(
                         "{crate_name} depends on '{dep_crate_name}' (according to cargo-metadata) which we haven't compiled"
                     );
                 }
@@ -216,8 +327,11 @@ impl Checker {
         }
 
         if deduced_caps.caps.keys().any(Capability::is_critical) {
-            // If we have critical capabilities, all the others are uninteresting
-            deduced_caps.caps.retain(|key, _| key.is_critical());
+            // If we have critical capabilities, all the others are uninteresting,
+            // except ffi, which tells us what the unsafe code is probably for.
+            deduced_caps
+                .caps
+                .retain(|key, _| key.is_critical() || *key == Capability::Ffi);
         }
 
         Ok(deduced_caps)
@@ -226,7 +340,7 @@ impl Checker {
     /// NOTE: each crate can have multiple artifacts, e.g. both a `custom-build` (build.rs)
     /// and a library.
     ///
-    /// Returns `true` if we printed anything
+    /// Returns `true` if the artifact passed the check, i.e. it has no capabilities it wasn't granted.
     fn add_artifact(
         &self,
         output: &mut CheckerOutput,
@@ -234,7 +348,6 @@ impl Checker {
         artifact: &Artifact,
         bin_path: &Utf8Path,
         dep_kinds: &DepKindSet,
-        verbose: bool,
     ) -> anyhow::Result<bool> {
         let crate_name = CrateName::new(package.name.to_string())?;
 
@@ -262,7 +375,38 @@ impl Checker {
             deduced_caps.caps.entry(Capability::BuildRs).or_default();
         }
 
-        let allowed_caps = self.config.crate_caps(&crate_name);
+        let crate_target = CrateTarget {
+            package_id: &package.id,
+            name: &crate_name,
+            version: &package.version,
+            kind: &artifact.target.kind[0],
+            dep_kinds,
+            features: &artifact.features,
+        };
+        let allowed_caps = self.config.crate_caps(&crate_target);
+        let denials = self
+            .config
+            .denials(&crate_target, &deduced_caps.caps.keys().copied().collect());
+        let undeclared = undeclared_caps(package, &artifact.target.kind, &deduced_caps);
+        let mut violations = filter_capabilities(&deduced_caps, &allowed_caps);
+        violations.extend(denials.keys().copied());
+        // NOTE: `undeclared` are only warnings. The crate authors may have forgotten
+        // to update their declaration, and nothing in the workspace config could fix that.
+
+        output.add_checked(CheckedArtifact {
+            package_id: package.id.clone(),
+            crate_name: crate_name.clone(),
+            version: package.version.clone(),
+            manifest_path: package.manifest_path.clone(),
+            target_kind: artifact.target.kind[0].clone(),
+            dep_kinds: dep_kinds.clone(),
+            features: artifact.features.clone(),
+            caps: deduced_caps.clone(),
+            allowed: allowed_caps.clone(),
+            violations: violations.clone(),
+            denials,
+            undeclared,
+        });
 
         let crate_kind_suffix = {
             if artifact.target.kind.contains(&TargetKind::CustomBuild) {
@@ -278,35 +422,42 @@ impl Checker {
             }
         };
 
-        let critical_caps = deduced_caps
-            .caps
+        let passed = violations.is_empty();
+
+        // If we violate with critical capabilities, all the others are uninteresting to a human.
+        let critical_violations = violations
             .iter()
-            .filter(|(c, _)| c.is_critical())
-            .map(|(c, reasons)| format!("{} {c} because of {}", c.emoji(), format_reasons(reasons)))
+            .filter(|c| c.is_critical())
+            .filter_map(|c| {
+                let reasons = deduced_caps.caps.get(c)?;
+                Some(format!(
+                    "{} {c} because of {}",
+                    c.emoji(),
+                    format_reasons(reasons)
+                ))
+            })
             .collect_vec();
 
-        let info = if critical_caps.is_empty() {
-            let filtered_caps = filter_capabilities(&deduced_caps, &allowed_caps);
+        if self.verbosity.quiet {
+            return Ok(passed);
+        }
 
-            if filtered_caps.is_empty() {
-                if self.show_empty {
-                    "😌 none".to_owned()
-                } else {
-                    return Ok(false); // TODO: respect verbose? maybe?
-                }
-            } else {
-                let cap_names: Vec<String> = filtered_caps
-                    .iter()
-                    .map(|cap| format!("{}{cap}", cap.emoji()))
-                    .collect();
-                cap_names.join(", ")
-            }
+        let info = if !critical_violations.is_empty() {
+            critical_violations.join(", ")
+        } else if !passed {
+            let cap_names: Vec<String> = violations
+                .iter()
+                .map(|cap| format!("{}{cap}", cap.emoji()))
+                .collect();
+            cap_names.join(", ")
+        } else if self.verbosity.show_empty {
+            "😌 none".to_owned()
         } else {
-            critical_caps.join(", ")
+            return Ok(passed); // TODO: respect verbose? maybe?
         };
 
         println!("{crate_name}{crate_kind_suffix}: {info}");
-        if verbose {
+        if self.verbosity.verbose {
             println!("  source: {}", artifact.target.src_path);
             println!("  path: {}", as_relative_path(bin_path));
 
@@ -323,6 +474,12 @@ impl Checker {
             );
             println!("  Crate kind: {}", dep_kinds.kind.iter().join(", "));
 
+            for (cap, reasons) in &deduced_caps.caps {
+                if !reasons.is_empty() {
+                    println!("  {} {cap}: {}", cap.emoji(), format_reasons(reasons));
+                }
+            }
+
             if !artifact.target.kind.contains(&TargetKind::CustomBuild) && has_build_rs(package) {
                 let build_rs_caps = output
                     .crate_caps
@@ -341,11 +498,11 @@ impl Checker {
             println!();
         }
 
-        Ok(true)
+        Ok(passed)
     }
 }
 
-fn as_relative_path(path: &Utf8Path) -> &Utf8Path {
+pub fn as_relative_path(path: &Utf8Path) -> &Utf8Path {
     if let Ok(cwd) = std::env::current_dir()
         && let Ok(relative) = path.strip_prefix(cwd)
     {
@@ -356,22 +513,302 @@ fn as_relative_path(path: &Utf8Path) -> &Utf8Path {
 }
 
 /// Filter capabilities by removing allowed ones, keeping only the non-allowed ones.
+///
+/// The wildcard only allows non-critical capabilities.
+/// See [`CapabilitySet::covers`] for umbrella capabilities like `fs`.
+/// Critical capabilities must be granted explicitly.
 fn filter_capabilities(actual_caps: &DeducedCaps, allowed_caps: &CapabilitySet) -> CapabilitySet {
-    let actual_caps: CapabilitySet = actual_caps.caps.keys().copied().collect();
+    let allow_all = allowed_caps.contains(&Capability::Wildcard);
+
+    actual_caps
+        .caps
+        .keys()
+        .filter(|&&cap| !allowed_caps.covers(cap) && (cap.is_critical() || !allow_all))
+        .copied()
+        .collect()
+}
 
-    if allowed_caps.contains(&Capability::Wildcard) {
-        CapabilitySet::default()
-    } else {
-        actual_caps
-            .iter()
-            .filter(|cap| !allowed_caps.contains(cap))
-            .copied()
-            .collect()
+/// The capabilities of the library of `package` beyond what it declares in its own `[package.metadata.caps]`.
+///
+/// These are warnings, not policy violations.
+///
+/// The declaration is about what the library does,
+/// so the `build.rs` and proc-macros of the package are not checked against it.
+fn undeclared_caps(
+    package: &Package,
+    target_kinds: &[TargetKind],
+    deduced_caps: &DeducedCaps,
+) -> CapabilitySet {
+    let is_lib = target_kinds.iter().any(|kind| {
+        matches!(
+            kind,
+            TargetKind::Lib
+                | TargetKind::RLib
+                | TargetKind::DyLib
+                | TargetKind::CDyLib
+                | TargetKind::StaticLib
+        )
+    });
+    if !is_lib {
+        return CapabilitySet::default();
+    }
+
+    match declared_caps(&package.metadata) {
+        Ok(Some(declared)) => {
+            let mut undeclared = filter_capabilities(deduced_caps, &declared);
+            // Not something the library does, just that the package has a build.rs:
+            undeclared.remove(&Capability::BuildRs);
+            undeclared
+        }
+        Ok(None) => CapabilitySet::default(),
+        Err(err) => {
+            // Perhaps another tool uses the same table for something else.
+            eprintln!(
+                "WARNING: Ignoring `declared` in [package.metadata.caps] of {}: {err:#}",
+                package.manifest_path
+            );
+            CapabilitySet::default()
+        }
     }
 }
 
+/// The capabilities a crate declares it has, in `declared` of its own `[package.metadata.caps]`.
+///
+/// This is a separate key from the rules of the workspace config that a workspace member can have in the same table.
+///
+/// This lets the authors of a crate tell its users what it is supposed to do,
+/// so that we can flag it if it suddenly does more.
+fn declared_caps(package_metadata: &serde_json::Value) -> anyhow::Result<Option<CapabilitySet>> {
+    package_metadata
+        .get("caps")
+        .and_then(|table| table.get("declared"))
+        .map(|caps| serde_json::from_value(caps.clone()))
+        .transpose()
+        .context("Expected a list of capabilities, e.g. declared = [\"alloc\", \"net\"]")
+}
+
 fn deduce_caps_of_binary(rules: &SymbolRules, path: &Utf8Path) -> anyhow::Result<DeducedCaps> {
     let symbols = crate::extract_symbols(path)?;
     let filtered_symbols = crate::filter_symbols(symbols, false, false);
     DeducedCaps::from_symbols(rules, filtered_symbols)
 }
+
+#[cfg(test)]
+impl CheckedArtifact {
+    /// The library of `name` 1.0.0, used as a normal dependency,
+    /// which has each of `caps` because of its source code at `{name}/src/lib.rs:1`.
+    pub fn test_lib(name: &str, caps: &[Capability]) -> Self {
+        let reason = Reason::SourceCodeAnalysis {
+            location: crate::capability::SourceLocation {
+                path: std::sync::Arc::new(format!("{name}/src/lib.rs").into()),
+                line_nr: 1,
+            },
+        };
+        Self {
+            package_id: PackageId {
+                repr: name.to_owned(),
+            },
+            crate_name: CrateName::new(name).expect("Bad crate name"),
+            version: Version::new(1, 0, 0),
+            manifest_path: format!("{name}/Cargo.toml").into(),
+            target_kind: TargetKind::Lib,
+            dep_kinds: DepKindSet {
+                kind: core::iter::once(DepKind::Normal).collect(),
+            },
+            features: vec![],
+            caps: DeducedCaps {
+                caps: caps
+                    .iter()
+                    .map(|&cap| (cap, core::iter::once(reason.clone()).collect()))
+                    .collect(),
+                unresolved_crates: Default::default(),
+            },
+            allowed: CapabilitySet::default(),
+            violations: CapabilitySet::default(),
+            denials: BTreeMap::default(),
+            undeclared: CapabilitySet::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(caps: &[Capability]) -> CapabilitySet {
+        caps.iter().copied().collect()
+    }
+
+    fn deduced(caps: &[Capability]) -> DeducedCaps {
+        DeducedCaps {
+            caps: caps.iter().map(|&cap| (cap, Default::default())).collect(),
+            unresolved_crates: Default::default(),
+        }
+    }
+
+    /// Build the `test_caps` crate with some of its features,
+    /// and check that we find the capabilities they exercise.
+    #[test]
+    fn test_test_caps() {
+        let workspace_dir = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+        let target_dir = tempfile::tempdir().unwrap();
+        let target_dir = Utf8Path::from_path(target_dir.path()).unwrap();
+        let rules = SymbolRules::load_default();
+
+        let caps_of = |feature: &str| -> CapabilitySet {
+            let status = std::process::Command::new(
+                std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()),
+            )
+            .current_dir(&workspace_dir)
+            .args(["build", "--quiet", "--release", "-p", "test_caps"])
+            .args(["--features", feature, "--target-dir", target_dir.as_str()])
+            .status()
+            .unwrap();
+            assert!(
+                status.success(),
+                "Failed to build test_caps with {feature:?}"
+            );
+            deduce_caps_of_binary(&rules, &target_dir.join("release/libtest_caps.rlib"))
+                .unwrap()
+                .caps
+                .into_keys()
+                .collect()
+        };
+
+        let fread = caps_of("fread");
+        assert!(fread.contains(&Capability::FsRead), "{fread:?}");
+        assert!(!fread.contains(&Capability::FsWrite), "{fread:?}");
+
+        let fwrite = caps_of("fwrite");
+        assert!(fwrite.contains(&Capability::FsWrite), "{fwrite:?}");
+        assert!(!fwrite.covers(Capability::FsRead), "{fwrite:?}");
+
+        let fs = caps_of("fs");
+        assert!(
+            fs.covers(Capability::FsWrite),
+            "Creating and appending to files must not look like only reading: {fs:?}"
+        );
+
+        let net = caps_of("net");
+        assert!(net.contains(&Capability::NetListen), "{net:?}");
+        assert!(net.contains(&Capability::NetDns), "{net:?}");
+        assert!(!net.contains(&Capability::NetConnect), "{net:?}");
+    }
+
+    #[test]
+    fn test_filter_capabilities() {
+        let actual = deduced(&[Capability::Alloc, Capability::Net, Capability::Unsafe]);
+
+        assert_eq!(
+            filter_capabilities(&actual, &set(&[Capability::Alloc])),
+            set(&[Capability::Net, Capability::Unsafe])
+        );
+
+        assert_eq!(
+            filter_capabilities(&actual, &set(&[Capability::Wildcard])),
+            set(&[Capability::Unsafe]),
+            "The wildcard should not grant critical capabilities"
+        );
+
+        assert!(
+            filter_capabilities(&actual, &set(&[Capability::Wildcard, Capability::Unsafe]))
+                .is_empty(),
+            "Critical capabilities can be granted by name"
+        );
+
+        let fs = deduced(&[Capability::FS, Capability::FsRead, Capability::FsWrite]);
+        assert!(
+            filter_capabilities(&fs, &set(&[Capability::FS])).is_empty(),
+            "fs should grant both fs.read and fs.write"
+        );
+        assert_eq!(
+            filter_capabilities(&fs, &set(&[Capability::FsRead])),
+            set(&[Capability::FS, Capability::FsWrite]),
+            "fs.read should not grant an fs that could be a write"
+        );
+        assert!(
+            filter_capabilities(&fs, &set(&[Capability::FsRead, Capability::FsWrite])).is_empty(),
+            "fs.read and fs.write together should grant fs"
+        );
+
+        let listen = deduced(&[Capability::NetListen]);
+        assert_eq!(
+            filter_capabilities(&listen, &set(&[Capability::NetConnect, Capability::NetDns])),
+            set(&[Capability::NetListen])
+        );
+        assert!(filter_capabilities(&listen, &set(&[Capability::Net])).is_empty());
+        assert_eq!(
+            filter_capabilities(
+                &deduced(&[Capability::Net]),
+                &set(&[Capability::NetConnect, Capability::NetDns])
+            ),
+            set(&[Capability::Net]),
+            "An unclassified net could be listening"
+        );
+    }
+
+    #[test]
+    fn test_declared_caps() {
+        assert_eq!(declared_caps(&serde_json::Value::Null).unwrap(), None);
+        assert_eq!(
+            declared_caps(&serde_json::json!({ "caps": { "rules": [] } })).unwrap(),
+            None,
+            "A workspace member with only rules does not declare its capabilities"
+        );
+        assert_eq!(
+            declared_caps(&serde_json::json!({ "caps": { "caps": ["alloc"] } })).unwrap(),
+            None,
+            "Only `declared` is a declaration"
+        );
+
+        let declared =
+            declared_caps(&serde_json::json!({ "caps": { "declared": ["alloc", "fs"] } }))
+                .unwrap()
+                .unwrap();
+        assert_eq!(declared, set(&[Capability::Alloc, Capability::FS]));
+        assert_eq!(
+            filter_capabilities(&deduced(&[Capability::Alloc, Capability::Net]), &declared),
+            set(&[Capability::Net])
+        );
+
+        assert!(declared_caps(&serde_json::json!({ "caps": { "declared": "fs" } })).is_err());
+    }
+
+    #[test]
+    fn test_undeclared_caps() {
+        let package = |metadata: serde_json::Value| -> Package {
+            serde_json::from_value(serde_json::json!({
+                "name": "acme",
+                "version": "1.0.0",
+                "id": "acme",
+                "dependencies": [],
+                "targets": [],
+                "features": {},
+                "manifest_path": "acme/Cargo.toml",
+                "metadata": metadata,
+            }))
+            .unwrap()
+        };
+        let declared = package(serde_json::json!({ "caps": { "declared": ["alloc"] } }));
+        let caps = deduced(&[Capability::Alloc, Capability::BuildRs, Capability::Command]);
+
+        assert_eq!(
+            undeclared_caps(&declared, &[TargetKind::Lib], &caps),
+            set(&[Capability::Command]),
+            "Having a build.rs is not something the library does"
+        );
+        assert!(
+            undeclared_caps(&declared, &[TargetKind::CustomBuild], &caps).is_empty(),
+            "The declaration is only about the library"
+        );
+        assert!(
+            undeclared_caps(
+                &package(serde_json::json!({ "caps": { "declared": "other tool" } })),
+                &[TargetKind::Lib],
+                &caps
+            )
+            .is_empty(),
+            "A table we don't understand should be ignored"
+        );
+    }
+}
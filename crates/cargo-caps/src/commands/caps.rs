@@ -1,9 +1,21 @@
 use cargo_metadata::camino::Utf8PathBuf;
 
 use crate::{
-    cap_rule::SymbolRules, capability::DeducedCaps, reservoir_sample::ReservoirSampleExt as _,
+    cap_rule::SymbolRules, capability::DeducedCaps, config::WorkspaceConfig, report::BinaryReport,
+    reservoir_sample::ReservoirSampleExt as _,
 };
 
+/// How to present the results of `cargo-caps caps`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CapsFormat {
+    /// Human-readable text, with a sample of the reasons.
+    #[default]
+    Text,
+
+    /// A versioned JSON report (see `report.rs`) with all the reasons.
+    Json,
+}
+
 #[derive(clap::Parser)]
 pub struct CapsCommand {
     /// Path to the binary file
@@ -20,6 +32,14 @@ pub struct CapsCommand {
     /// Show detailed reasoning for each capability
     #[arg(short, long, default_value = "false")]
     pub verbose: bool,
+
+    /// How to present the results
+    #[arg(long = "format", value_enum, default_value_t)]
+    pub format: CapsFormat,
+
+    /// Also use the `symbols` rules of this workspace config, e.g. `cargo-caps.eon`
+    #[arg(long = "config")]
+    pub config: Option<Utf8PathBuf>,
 }
 
 impl CapsCommand {
@@ -28,7 +48,11 @@ impl CapsCommand {
             anyhow::bail!("Binary file does not exist: {}", self.binary_path);
         }
 
-        let rules = SymbolRules::load_default();
+        let rules = if let Some(config) = &self.config {
+            SymbolRules::load(&WorkspaceConfig::from_path(config)?)
+        } else {
+            SymbolRules::load_default()
+        };
 
         // Extract symbols from the binary
         let symbols = crate::extract_symbols(&self.binary_path)?;
@@ -39,7 +63,13 @@ impl CapsCommand {
         let capabilities = DeducedCaps::from_symbols(&rules, filtered_symbols)?;
 
         // Print results
-        self.print_capabilities(&capabilities);
+        match self.format {
+            CapsFormat::Text => self.print_capabilities(&capabilities),
+            CapsFormat::Json => {
+                let report = BinaryReport::new(&self.binary_path, &capabilities);
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+        }
 
         Ok(())
     }
@@ -12,17 +12,43 @@ use cargo_metadata::{
 use itertools::Itertools as _;
 
 use crate::{
-    build_graph_analysis::DepKindSet,
+    build_graph_analysis::{DepGraph, DepKindSet},
     cap_rule::SymbolRules,
-    checker::{Checker, CheckerOutput},
+    checker::{Checker, CheckerOutput, Verbosity},
     config::WorkspaceConfig,
+    date::Date,
+    dot::dot_graph,
+    lockfile::{CapsChange, Lockfile},
+    report::Report,
+    sarif::sarif_report,
 };
 
-#[derive(clap::Parser)]
-pub struct CheckCommand {
-    #[arg(short = 'v', long = "verbose")]
-    pub verbose: bool,
+/// How to present the results of `cargo-caps check`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+
+    /// A versioned JSON report (see `report.rs`) printed to stdout when done.
+    ///
+    /// Everything else is printed to stderr.
+    Json,
+
+    /// A SARIF 2.1 log of all violations, for code-scanning tools.
+    ///
+    /// Everything else is printed to stderr.
+    Sarif,
+
+    /// A Graphviz DOT graph of the dependencies, showing how capabilities propagate through it.
+    ///
+    /// Everything else is printed to stderr.
+    Dot,
+}
 
+/// How to build the workspace, and what config to check it against.
+#[derive(clap::Args)]
+pub struct BuildArgs {
     #[arg(short = 'p', long = "package")]
     pub package: Option<String>,
 
@@ -38,6 +64,28 @@ pub struct CheckCommand {
     #[arg(long = "release")]
     pub release: bool,
 
+    /// Where to load the config file for the current workspace
+    #[arg(long = "config", default_value = "cargo-caps.eon")]
+    pub config: Utf8PathBuf,
+}
+
+/// The result of [`BuildArgs::build_and_check`].
+pub struct Analysis {
+    pub checker: Checker,
+    pub output: CheckerOutput,
+
+    /// Did `cargo build` succeed?
+    pub build_succeeded: bool,
+}
+
+#[derive(clap::Parser)]
+pub struct CheckCommand {
+    #[command(flatten)]
+    pub build: BuildArgs,
+
+    #[arg(short = 'v', long = "verbose")]
+    pub verbose: bool,
+
     #[arg(short = 'q', long = "quiet")]
     pub quiet: bool,
 
@@ -45,25 +93,217 @@ pub struct CheckCommand {
     #[arg(long = "show-empty")]
     pub show_empty: bool,
 
-    /// Where to load the config file for the current workspace
-    #[arg(long = "config", default_value = "cargo-caps.eon")]
-    pub config: Utf8PathBuf,
+    /// How to present the results
+    #[arg(long = "format", value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    /// Where to store the deduced capabilities of all packages
+    #[arg(long = "lockfile", default_value = "cargo-caps.lock")]
+    pub lockfile: Utf8PathBuf,
+
+    /// Write the deduced capabilities of all packages to the lockfile
+    #[arg(long = "update-lockfile")]
+    pub update_lockfile: bool,
+
+    /// Only report the capabilities that were added or removed since the lockfile was written
+    #[arg(long = "diff")]
+    pub diff: bool,
 }
 
 impl CheckCommand {
     pub fn execute(&self) -> anyhow::Result<()> {
-        let config = if self.config.exists() {
-            WorkspaceConfig::from_path(&self.config)?
+        let Analysis {
+            checker,
+            output,
+            build_succeeded,
+        } = self.build.build_and_check(Verbosity {
+            verbose: self.verbose,
+            show_empty: self.show_empty,
+            quiet: self.quiet || self.diff || self.format != ReportFormat::Text,
+        })?;
+
+        let lockfile = Lockfile::from_output(&output);
+        let changes = if self.diff {
+            Some(Lockfile::from_path(&self.lockfile)?.diff(&lockfile))
         } else {
+            None
+        };
+
+        match self.format {
+            ReportFormat::Text => {
+                if let Some(changes) = &changes {
+                    Self::print_changes(changes);
+                } else {
+                    Self::print_summary(&output);
+                }
+            }
+            ReportFormat::Json => {
+                let mut report = Report::new(&output);
+                report.lockfile_changes = changes;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            ReportFormat::Sarif => {
+                println!("{}", serde_json::to_string_pretty(&sarif_report(&output))?);
+            }
+            ReportFormat::Dot => {
+                let graph = DepGraph::from_cargo_metadata(&checker.metadata, &[])?;
+                print!("{}", dot_graph(&graph, &output));
+            }
+        }
+
+        if !build_succeeded {
+            // Don't overwrite the lockfile with the capabilities of a partial build.
+            anyhow::bail!("cargo build failed");
+        }
+
+        if self.update_lockfile {
+            lockfile.save(&self.lockfile)?;
+            eprintln!("Wrote {}", self.lockfile);
+        }
+
+        let undeclared = output
+            .checked
+            .iter()
+            .filter(|checked| !checked.undeclared.is_empty())
+            .collect_vec();
+        if !undeclared.is_empty() {
+            eprintln!();
+            eprintln!(
+                "WARNING: {} crate(s) have capabilities they don't declare in `declared` of their own [package.metadata.caps]:",
+                undeclared.len()
+            );
+            for checked in undeclared {
+                eprintln!(
+                    "  {} {} ({}): {}",
+                    checked.crate_name,
+                    checked.version,
+                    checked.manifest_path,
+                    checked.undeclared.iter().join(", ")
+                );
+            }
+        }
+
+        let violating = output
+            .checked
+            .iter()
+            .filter(|checked| !checked.violations.is_empty())
+            .sorted_by_key(|checked| checked.scope())
+            .collect_vec();
+        if !violating.is_empty() {
+            eprintln!();
+            eprintln!("{} artifact(s) violate the policy:", violating.len());
+            for (scope, group) in &violating.iter().chunk_by(|checked| checked.scope()) {
+                eprintln!("  {scope}:");
+                for checked in group {
+                    eprintln!(
+                        "    {} {} ({}): {}",
+                        checked.crate_name,
+                        checked.version,
+                        checked.target_kind,
+                        checked.violations.iter().join(", ")
+                    );
+                    for (cap, denials) in &checked.denials {
+                        eprintln!("      {cap} is forbidden by {}", denials.iter().join(", "));
+                    }
+                }
+            }
+            eprintln!();
+            anyhow::bail!(
+                "{} crate(s) have capabilities they were not granted in {:?}",
+                violating
+                    .iter()
+                    .map(|checked| &checked.package_id)
+                    .unique()
+                    .count(),
+                self.build.config
+            );
+        }
+
+        Ok(())
+    }
+
+    fn print_changes(changes: &[CapsChange]) {
+        if changes.is_empty() {
+            println!("No capabilities were added or removed since the lockfile was written");
+        } else {
+            println!("Capabilities changed since the lockfile was written:");
+            for change in changes {
+                println!("  {change}");
+            }
+        }
+    }
+
+    fn print_summary(output: &CheckerOutput) {
+        if 0 < output.num_artifacts_passed {
+            println!();
             println!(
-                "Expected config at {:?} - create one with 'cargo-caps init' or change the path with --config",
+                "{} artifact(s) passed the check",
+                output.num_artifacts_passed
+            );
+        }
+
+        println!();
+        println!(
+            "Run with -v/--verbose to get details about each dependency, or run `cargo-caps caps` with the path to a specific binary (executable, .rlib, .dylib, …) to learn more about it."
+        );
+    }
+}
+
+impl BuildArgs {
+    /// Load the config file, and add the config in the `Cargo.toml` metadata of the workspace.
+    pub fn load_config(&self, metadata: &Metadata) -> anyhow::Result<WorkspaceConfig> {
+        if let Some(config) = self.try_load_config(metadata)? {
+            Ok(config)
+        } else {
+            eprintln!(
+                "Expected config at {:?} or in [workspace.metadata.caps] of Cargo.toml - create one with 'cargo-caps init' or change the path with --config",
                 self.config
             );
-            println!();
-            WorkspaceConfig::allow_basics()
+            eprintln!();
+            Ok(WorkspaceConfig::allow_basics())
+        }
+    }
+
+    /// Like [`Self::load_config`], but returns `None` if there is no config.
+    pub fn try_load_config(&self, metadata: &Metadata) -> anyhow::Result<Option<WorkspaceConfig>> {
+        let mut config = if self.config.exists() {
+            WorkspaceConfig::from_path(&self.config)?
+        } else if WorkspaceConfig::has_metadata_config(metadata) {
+            WorkspaceConfig {
+                today: Some(Date::today()),
+                ..Default::default()
+            }
+        } else {
+            return Ok(None);
         };
+        config.extend_from_metadata(metadata)?;
+
+        for rule in config.expired_rules() {
+            eprintln!(
+                "WARNING: {rule} expired on {} and no longer applies - review it again and update `expires`",
+                rule.expires
+                    .map(|date| date.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        Ok(Some(config))
+    }
 
+    /// Run `cargo build`, and check each artifact as it is built.
+    pub fn build_and_check(&self, verbosity: Verbosity) -> anyhow::Result<Analysis> {
         let metadata = self.gather_cargo_metadata()?;
+        let config = self.load_config(&metadata)?;
+        self.build_and_check_with_config(metadata, config, verbosity)
+    }
+
+    /// Like [`Self::build_and_check`], but with an already loaded config.
+    pub fn build_and_check_with_config(
+        &self,
+        metadata: Metadata,
+        mut config: WorkspaceConfig,
+        verbosity: Verbosity,
+    ) -> anyhow::Result<Analysis> {
+        config.resolve_trees(&metadata)?;
         let crate_infos = self.calc_crate_kinds(&metadata)?;
 
         // TODO: before starting the actual build,
@@ -72,8 +312,6 @@ impl CheckCommand {
 
         let mut cmd = self.make_cargo_command();
 
-        let verbose = self.verbose;
-
         let mut child = cmd.stdout(Stdio::piped()).spawn()?;
 
         let stdout = child
@@ -83,12 +321,13 @@ impl CheckCommand {
         let reader = BufReader::new(stdout);
 
         let checker = Checker {
-            rules: SymbolRules::load_default(),
+            rules: SymbolRules::load(&config),
             config,
             metadata,
-            show_empty: self.show_empty,
+            verbosity,
         };
         let mut output = CheckerOutput::default();
+        let mut build_succeeded = true;
 
         for line in reader.lines() {
             let line = line?;
@@ -96,7 +335,7 @@ impl CheckCommand {
                 match message {
                     Message::CompilerArtifact(artifact) => {
                         checker
-                            .analyze_artifact(&mut output, &crate_infos, verbose, &artifact)
+                            .analyze_artifact(&mut output, &crate_infos, &artifact)
                             .with_context(|| format!("target name: {}", artifact.target.name))?;
                     }
                     Message::CompilerMessage(compiler_message) => {
@@ -107,45 +346,43 @@ impl CheckCommand {
                                 | DiagnosticLevel::Help
                         );
                         if show {
-                            println!("CompilerMessage: {compiler_message}");
+                            eprintln!("CompilerMessage: {compiler_message}");
                         }
                     }
                     Message::BuildScriptExecuted(build_script) => {
-                        if false {
-                            println!("BuildScriptExecuted: {build_script:?}");
+                        // `cargo:rustc-link-lib=…`. The build script runs before the library is built,
+                        // so this is known when we check the library.
+                        if !build_script.linked_libs.is_empty() {
+                            output
+                                .linked_libs
+                                .entry(build_script.package_id)
+                                .or_default()
+                                .extend(build_script.linked_libs.iter().map(ToString::to_string));
                         }
                     }
                     Message::BuildFinished(build_finished) => {
                         if build_finished.success {
-                            println!("Build finished successfully");
+                            eprintln!("Build finished successfully");
                         } else {
-                            println!("Build failed"); // TODO: return error
+                            eprintln!("Build failed");
+                            build_succeeded = false;
                         }
                     }
                     Message::TextLine(text_line) => {
-                        println!("TextLine: {text_line}");
+                        eprintln!("TextLine: {text_line}");
                     }
                     _ => {}
                 }
             }
         }
 
-        child.wait()?;
+        let status = child.wait()?;
 
-        if 0 < output.num_artifacts_passed {
-            println!();
-            println!(
-                "{} artifact(s) passed the check",
-                output.num_artifacts_passed
-            );
-        }
-
-        println!();
-        println!(
-            "Run with -v/--verbose to get details about each dependency, or run `cargo-caps caps` with the path to a specific binary (executable, .rlib, .dylib, …) to learn more about it."
-        );
-
-        Ok(())
+        Ok(Analysis {
+            checker,
+            output,
+            build_succeeded: build_succeeded && status.success(),
+        })
     }
 
     fn calc_crate_kinds(
@@ -183,7 +420,7 @@ impl CheckCommand {
         anyhow::bail!("Failed to locate manifest path of package '{crate_name}'");
     }
 
-    fn gather_cargo_metadata(&self) -> anyhow::Result<cargo_metadata::Metadata> {
+    pub fn gather_cargo_metadata(&self) -> anyhow::Result<cargo_metadata::Metadata> {
         let mut metadata_cmd = MetadataCommand::new();
         if let Some(package) = &self.package {
             metadata_cmd.manifest_path(Self::cargo_toml_path_of_package(package)?);
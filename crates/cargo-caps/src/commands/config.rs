@@ -0,0 +1,97 @@
+use anyhow::Context as _;
+
+use crate::{
+    CrateName,
+    checker::Verbosity,
+    commands::check::BuildArgs,
+    config_check::{Severity, check_config},
+};
+
+#[derive(clap::Parser)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    pub command: ConfigSubcommand,
+}
+
+#[derive(clap::Subcommand)]
+pub enum ConfigSubcommand {
+    /// Print the effective config, with all includes and `Cargo.toml` metadata merged
+    Print {
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+
+    /// Find problems in the config, like rules for crates that are not in the dependency graph,
+    /// duplicate rules, grants of capabilities no crate has, and grants of critical capabilities
+    Check {
+        #[command(flatten)]
+        build: BuildArgs,
+
+        /// Don't build the workspace. Faster, but then we can't find grants of capabilities no crate has.
+        #[arg(long = "no-build")]
+        no_build: bool,
+    },
+}
+
+impl ConfigCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        match &self.command {
+            ConfigSubcommand::Print { build } => {
+                let config = build.load_config(&build.gather_cargo_metadata()?)?;
+                let eon = eon::to_string(&config, &Default::default())
+                    .context("Failed to serialize config")?;
+                println!("{eon}");
+            }
+            ConfigSubcommand::Check { build, no_build } => Self::check(build, *no_build)?,
+        }
+        Ok(())
+    }
+
+    fn check(build: &BuildArgs, no_build: bool) -> anyhow::Result<()> {
+        let (config, metadata, analysis) = if no_build {
+            let metadata = build.gather_cargo_metadata()?;
+            let mut config = build.load_config(&metadata)?;
+            config.resolve_trees(&metadata)?;
+            (config, metadata, None)
+        } else {
+            let analysis = build.build_and_check(Verbosity {
+                quiet: true,
+                ..Default::default()
+            })?;
+            if !analysis.build_succeeded {
+                anyhow::bail!("cargo build failed");
+            }
+            (
+                analysis.checker.config,
+                analysis.checker.metadata,
+                Some(analysis.output),
+            )
+        };
+
+        let packages = metadata
+            .packages
+            .iter()
+            .map(|package| {
+                Ok((
+                    CrateName::new(package.name.to_string())?,
+                    package.version.clone(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let findings = check_config(&config, &packages, analysis.as_ref());
+        for finding in &findings {
+            println!("{finding}");
+        }
+
+        let num_warnings = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Warning)
+            .count();
+        if 0 < num_warnings {
+            anyhow::bail!("Found {num_warnings} problem(s) in {:?}", build.config);
+        }
+        println!("No problems found in {:?}", build.config);
+        Ok(())
+    }
+}
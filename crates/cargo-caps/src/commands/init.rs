@@ -1,25 +1,37 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
 
 use anyhow::Context as _;
+use cargo_metadata::TargetKind;
+use itertools::Itertools as _;
 
-#[derive(clap::Args)]
-pub struct InitCommand;
+use crate::{
+    Capability, CrateName,
+    capability::{Reasons, format_reasons},
+    checker::{CheckerOutput, Verbosity},
+    commands::check::BuildArgs,
+    config::WorkspaceConfig,
+};
 
-impl InitCommand {
-    #[expect(clippy::unused_self)]
-    pub fn execute(self) -> anyhow::Result<()> {
-        let config_path = Path::new("cargo-caps.eon");
-
-        if config_path.exists() {
-            anyhow::bail!("cargo-caps.eon already exists");
-        }
+/// The capabilities [`InitCommand::from_current`] grants to all crates.
+const BASIC_CAPS: [Capability; 2] = [Capability::Alloc, Capability::Panic];
 
-        let default_config = r#"// Configuration file for cargo-caps.
+const DEFAULT_CONFIG: &str = r#"// Configuration file for cargo-caps.
 // See https://crates.io/crates/cargo-caps
 // The capabilities are additive:
 // if a crate matches several rules, the crate is granted
 // the union of the capabilities afforded by all the rules.
+// Shared policy files can be loaded first with e.g. `include: ["../policy/base.eon"]`.
+// Crates can be matched by name ("tokio"), glob ("aws_sdk_*") or regex ("/^windows_(sys|targets)$/"),
+// and restricted to some versions, e.g. "tokio@^1.40" or "openssl@=0.10.66".
+// A rule can be restricted to some artifacts of the crates with e.g. `targets: ["custom-build"]`
+// (the build.rs), or "lib", "proc-macro", "bin".
+// A rule can be restricted to when some features of the crate are enabled, with e.g. `features: ["cookies"]`.
+// A rule can record why and by whom it was approved, and when it must be reviewed again, with
+// `reason: "…", reviewed_by: "…", reviewed_at: "2025-03-14", expires: "2026-03-14"`.
+// Expired grants no longer apply.
+// A rule can be restricted to crates that are only used in some ways with e.g. `dep_kinds: ["dev"]`
+// (or "normal", "build", "proc-macro").
 rules: [
 	{
 		// Capabilities all crates are allowed:
@@ -38,13 +50,222 @@ rules: [
 		crates: []
 	}
 ]
+// Deny rules forbid capabilities, even if they were granted above, e.g.
+// { caps: ["net"], crates: ["*"], except: ["reqwest"], reason: "Only reqwest may talk to the network" }
+// `trees: ["my_crate"]` applies a deny rule to a crate and all its dependencies.
+deny: []
+// Extra symbol rules, e.g. for internal C libraries or private crate paths.
+// These win over equally specific rules in the built-in default_rules.eon, e.g.
+// { caps: ["net"], patterns: ["acme_rpc_*"] }
+symbols: []
 "#;
 
-        fs::write(config_path, default_config)
-            .with_context(|| "Failed to create cargo-caps.eon file")?;
+#[derive(clap::Args)]
+pub struct InitCommand {
+    /// Build the workspace, and grant each crate exactly the capabilities it has today
+    #[arg(long = "from-current")]
+    pub from_current: bool,
+
+    #[command(flatten)]
+    pub build: BuildArgs,
+}
+
+impl InitCommand {
+    pub fn execute(self) -> anyhow::Result<()> {
+        let config_path = &self.build.config;
+
+        if config_path.exists() {
+            anyhow::bail!("{config_path} already exists");
+        }
 
-        println!("Created cargo-caps.eon");
+        let config = if self.from_current {
+            // There is no config file yet, so don't ask for one.
+            let metadata = self.build.gather_cargo_metadata()?;
+            let config = self
+                .build
+                .try_load_config(&metadata)?
+                .unwrap_or_else(WorkspaceConfig::allow_basics);
+            let analysis = self.build.build_and_check_with_config(
+                metadata,
+                config,
+                Verbosity {
+                    quiet: true,
+                    ..Default::default()
+                },
+            )?;
+            if !analysis.build_succeeded {
+                anyhow::bail!("cargo build failed");
+            }
+            config_from_output(&analysis.output)
+        } else {
+            DEFAULT_CONFIG.to_owned()
+        };
+
+        fs::write(config_path, config)
+            .with_context(|| format!("Failed to create {config_path}"))?;
+
+        println!("Created {config_path}");
         println!("Try running 'cargo-caps check'");
         Ok(())
     }
 }
+
+type CrateCaps = BTreeMap<Capability, Reasons>;
+
+/// A config that grants every crate exactly the capabilities it has in `output`.
+///
+/// Crates with the same capabilities are grouped into one rule,
+/// with comments listing some of the reasons for them.
+/// Capabilities that only the build.rs of a crate has are only granted to its build.rs.
+fn config_from_output(output: &CheckerOutput) -> String {
+    use core::fmt::Write as _;
+
+    // All versions of a crate are granted the same capabilities.
+    let mut crates: BTreeMap<&CrateName, CrateCaps> = BTreeMap::new();
+    let mut build_scripts: BTreeMap<&CrateName, CrateCaps> = BTreeMap::new();
+    for checked in &output.checked {
+        let caps = if checked.target_kind == TargetKind::CustomBuild {
+            build_scripts.entry(&checked.crate_name).or_default()
+        } else {
+            crates.entry(&checked.crate_name).or_default()
+        };
+        for (&cap, reasons) in &checked.caps.caps {
+            if !BASIC_CAPS.contains(&cap) {
+                caps.entry(cap).or_default().extend(reasons.iter().cloned());
+            }
+        }
+    }
+    for (crate_name, build_caps) in &mut build_scripts {
+        if let Some(caps) = crates.get(crate_name) {
+            build_caps.retain(|cap, _| !caps.contains_key(cap));
+        }
+    }
+
+    // Grouped by whether they are for build scripts, and then the capabilities:
+    type GroupKey = (bool, Vec<Capability>);
+    let mut groups: BTreeMap<GroupKey, Vec<(&CrateName, CrateCaps)>> = BTreeMap::new();
+    for (is_build_script, crates) in [(false, crates), (true, build_scripts)] {
+        for (crate_name, caps) in crates {
+            if !caps.is_empty() {
+                let cap_set = caps.keys().copied().collect_vec();
+                groups
+                    .entry((is_build_script, cap_set))
+                    .or_default()
+                    .push((crate_name, caps));
+            }
+        }
+    }
+
+    let mut config = String::new();
+    config.push_str(
+        "// Configuration file for cargo-caps, generated by `cargo-caps init --from-current`.\n\
+         // It grants each crate exactly the capabilities it had when it was generated.\n\
+         // See https://crates.io/crates/cargo-caps\n\
+         rules: [\n",
+    );
+    writeln!(
+        config,
+        "\t{{\n\t\t// Capabilities all crates are allowed:\n\t\tcrates: [\"*\"]\n\t\tcaps: [{}]\n\t}}",
+        BASIC_CAPS.iter().map(|cap| format!("{:?}", cap.to_string())).join(", ")
+    )
+    .ok();
+
+    for ((is_build_script, cap_set), crates) in groups {
+        writeln!(config, "\t{{").ok();
+        if is_build_script {
+            writeln!(config, "\t\ttargets: [\"custom-build\"]").ok();
+        }
+        writeln!(
+            config,
+            "\t\tcaps: [{}]",
+            cap_set
+                .iter()
+                .map(|cap| format!("{:?}", cap.to_string()))
+                .join(", ")
+        )
+        .ok();
+        writeln!(config, "\t\tcrates: [").ok();
+        for (crate_name, caps) in crates {
+            for (cap, reasons) in caps {
+                if reasons.is_empty() {
+                    continue; // e.g. `build.rs`
+                }
+                let reasons = format_reasons(&reasons).replace('\n', " ");
+                writeln!(config, "\t\t\t// {cap}: {reasons}").ok();
+            }
+            writeln!(config, "\t\t\t\"{crate_name}\"").ok();
+        }
+        writeln!(config, "\t\t]\n\t}}").ok();
+    }
+    config.push_str("]\n");
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{capability::DeducedCaps, checker::CheckedArtifact};
+
+    use super::*;
+
+    #[test]
+    fn test_config_from_output() {
+        let output = CheckerOutput {
+            checked: vec![
+                CheckedArtifact::test_lib("itoa", &[Capability::Alloc]),
+                CheckedArtifact::test_lib("hyper", &[Capability::Alloc, Capability::Net]),
+                CheckedArtifact::test_lib("reqwest", &[Capability::Net]),
+                CheckedArtifact::test_lib("libc", &[Capability::Unsafe]),
+                CheckedArtifact {
+                    caps: DeducedCaps {
+                        caps: [(Capability::BuildRs, Default::default())].into(),
+                        ..Default::default()
+                    },
+                    ..CheckedArtifact::test_lib("libc", &[])
+                },
+                CheckedArtifact {
+                    target_kind: TargetKind::CustomBuild,
+                    ..CheckedArtifact::test_lib("libc", &[Capability::Unsafe, Capability::Command])
+                },
+                CheckedArtifact {
+                    target_kind: TargetKind::CustomBuild,
+                    ..CheckedArtifact::test_lib("cc", &[Capability::Command])
+                },
+            ],
+            ..Default::default()
+        };
+
+        let config_str = config_from_output(&output);
+        assert!(
+            config_str.contains("\t\t\t// net: source code: hyper/src/lib.rs:1\n\t\t\t\"hyper\"\n"),
+            "{config_str}"
+        );
+
+        let config: WorkspaceConfig = eon::from_str(&config_str).unwrap();
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                format!(
+                    "{} to {}{}",
+                    rule.caps.iter().join(", "),
+                    rule.crates.iter().join(", "),
+                    if rule.targets.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {:?}", rule.targets)
+                    }
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            rules,
+            [
+                "alloc, panic to *",
+                "build.rs, unsafe to libc",
+                "net to hyper, reqwest",
+                "command to cc, libc [CustomBuild]",
+            ],
+            "The build.rs of libc should only be granted what the library doesn't already have"
+        );
+    }
+}
@@ -1,12 +1,16 @@
 pub mod caps;
 pub mod check;
+pub mod config;
 pub mod init;
 pub mod symbols;
+pub mod why;
 
 pub use caps::CapsCommand;
 pub use check::CheckCommand;
+pub use config::ConfigCommand;
 pub use init::InitCommand;
 pub use symbols::SymbolCommand;
+pub use why::WhyCommand;
 
 #[derive(clap::Subcommand)]
 pub enum Commands {
@@ -18,6 +22,10 @@ pub enum Commands {
     #[command(name = "caps")]
     Caps(CapsCommand),
 
+    /// Inspect the workspace config
+    #[command(name = "config")]
+    Config(ConfigCommand),
+
     /// Create a default cargo-caps.eon configuration file
     #[command(name = "init")]
     Init(InitCommand),
@@ -25,4 +33,8 @@ pub enum Commands {
     /// Extract and analyze symbols of a binary
     #[command(name = "symbols")]
     Symbols(SymbolCommand),
+
+    /// Explain why a crate has a capability, by following its dependencies
+    #[command(name = "why")]
+    Why(WhyCommand),
 }
@@ -0,0 +1,177 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use cargo_metadata::TargetKind;
+use itertools::Itertools as _;
+
+use crate::{
+    Capability, CrateName,
+    capability::{DeducedCaps, Reason},
+    checker::{CheckerOutput, Verbosity},
+    commands::check::{Analysis, BuildArgs},
+};
+
+/// At most this many reasons are shown for each crate in the chain.
+const MAX_REASONS_PER_CRATE: usize = 3;
+
+#[derive(clap::Parser)]
+pub struct WhyCommand {
+    /// The crate to explain
+    pub crate_name: String,
+
+    /// The capability to explain, e.g. `net` or `fs`
+    pub capability: Capability,
+
+    #[command(flatten)]
+    pub build: BuildArgs,
+}
+
+impl WhyCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        let crate_name = CrateName::new(self.crate_name.clone())?;
+        let capability = self.capability;
+
+        let Analysis { output, .. } = self.build.build_and_check(Verbosity {
+            quiet: true,
+            ..Default::default()
+        })?;
+
+        let Some(targets) = output.crate_caps.get(&crate_name) else {
+            anyhow::bail!("cargo-caps did not check any crate called '{crate_name}'");
+        };
+
+        let mut found = false;
+        for (kind, caps) in targets {
+            if caps.caps.contains_key(&capability) {
+                found = true;
+                println!(
+                    "{crate_name} ({kind}) has {}{capability} because of:",
+                    capability.emoji()
+                );
+                for line in explain(&output, &crate_name, caps, capability) {
+                    println!("  {line}");
+                }
+                println!();
+            }
+        }
+
+        if !found {
+            let caps: BTreeSet<Capability> = targets
+                .values()
+                .flat_map(|caps| caps.caps.keys().copied())
+                .collect();
+            anyhow::bail!(
+                "{crate_name} does not have the capability '{capability}'. It has: {}",
+                caps.iter().join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Follow the [`Reason::Crate`] links from `root` down to the crates that
+/// has the capability for some other reason.
+///
+/// Returns one line per reason, e.g. `my_app -> reqwest -> hyper -> std::net::TcpStream::connect`.
+/// Each crate is only explained once, via the shortest chain.
+fn explain(
+    output: &CheckerOutput,
+    root: &CrateName,
+    root_caps: &DeducedCaps,
+    capability: Capability,
+) -> Vec<String> {
+    let mut lines = vec![];
+    let mut visited: BTreeSet<&CrateName> = core::iter::once(root).collect();
+    let mut queue = VecDeque::from([(vec![root], root_caps)]);
+
+    while let Some((chain, caps)) = queue.pop_front() {
+        let Some(reasons) = caps.caps.get(&capability) else {
+            continue;
+        };
+        let path = chain.iter().join(" -> ");
+
+        let mut direct_reasons = vec![];
+        for reason in reasons {
+            if let Reason::Crate(dependency) = reason {
+                if visited.insert(dependency) {
+                    let dependency_caps = output
+                        .crate_caps
+                        .get(dependency)
+                        .and_then(|targets| targets.get(&TargetKind::Lib));
+                    if let Some(dependency_caps) = dependency_caps {
+                        let mut chain = chain.clone();
+                        chain.push(dependency);
+                        queue.push_back((chain, dependency_caps));
+                    } else {
+                        lines.push(format!("{path} -> {dependency} (not analyzed)"));
+                    }
+                }
+            } else {
+                direct_reasons.push(reason);
+            }
+        }
+
+        for reason in direct_reasons.iter().take(MAX_REASONS_PER_CRATE) {
+            lines.push(format!("{path} -> {reason}"));
+        }
+        if MAX_REASONS_PER_CRATE < direct_reasons.len() {
+            lines.push(format!(
+                "{path} -> … and {} more",
+                direct_reasons.len() - MAX_REASONS_PER_CRATE
+            ));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use crate::capability::SourceLocation;
+
+    use super::*;
+
+    fn lib_caps(output: &mut CheckerOutput, name: &str, reasons: Vec<Reason>) -> CrateName {
+        let crate_name = CrateName::new(name).unwrap();
+        let caps = DeducedCaps {
+            caps: BTreeMap::from([(Capability::Net, reasons.into_iter().collect())]),
+            unresolved_crates: Default::default(),
+        };
+        output
+            .crate_caps
+            .entry(crate_name.clone())
+            .or_default()
+            .insert(TargetKind::Lib, caps);
+        crate_name
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut output = CheckerOutput::default();
+        let hyper = lib_caps(
+            &mut output,
+            "hyper",
+            vec![Reason::SourceCodeAnalysis {
+                location: SourceLocation {
+                    path: Arc::new("hyper/src/net.rs".into()),
+                    line_nr: 42,
+                },
+            }],
+        );
+        let reqwest = lib_caps(&mut output, "reqwest", vec![Reason::Crate(hyper.clone())]);
+        let my_app = lib_caps(
+            &mut output,
+            "my_app",
+            vec![Reason::Crate(reqwest), Reason::Crate(hyper)],
+        );
+
+        let root_caps = &output.crate_caps[&my_app][&TargetKind::Lib];
+        assert_eq!(
+            explain(&output, &my_app, root_caps, Capability::Net),
+            ["my_app -> hyper -> source: hyper/src/net.rs:42"],
+            "hyper should only be explained once, via the shortest chain"
+        );
+    }
+}
@@ -1,11 +1,55 @@
-use cargo_metadata::camino::Utf8Path;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
 
-use crate::{Capability, CapabilitySet, CrateName};
+use anyhow::Context as _;
+use cargo_metadata::{
+    Metadata, PackageId, TargetKind,
+    camino::{Utf8Path, Utf8PathBuf},
+    semver::{Version, VersionReq},
+};
+use itertools::Itertools as _;
+
+use crate::{
+    Capability, CapabilitySet, CrateName,
+    build_graph_analysis::{DepKind, DepKindSet},
+    cap_rule::{SerializedRule, SymbolRules},
+    date::Date,
+};
 
 /// What crates are allowed what capabilities?
-#[derive(serde::Deserialize)]
+#[derive(Default, serde::Deserialize, serde::Serialize)]
 pub struct WorkspaceConfig {
+    /// Other config files to load first, relative to this one.
+    ///
+    /// The rules of this file are added to theirs,
+    /// and its symbol rules win over equally specific ones from the included files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<Utf8PathBuf>,
+
+    #[serde(default)]
     pub rules: Vec<CrateRule>,
+
+    /// Capabilities that are forbidden, even if they were granted by [`Self::rules`].
+    #[serde(default)]
+    pub deny: Vec<DenyRule>,
+
+    /// Extra symbol rules, e.g. for internal C libraries or private crate paths.
+    ///
+    /// These are merged with `default_rules.eon`, and win over equally specific default rules.
+    #[serde(default)]
+    pub symbols: Vec<SerializedRule>,
+
+    /// [`Self::symbols`], with the locations they were defined at.
+    #[serde(skip)]
+    pub symbol_rules: SymbolRules,
+
+    /// The date to check [`CrateRule::expires`] against.
+    ///
+    /// Set to today by [`Self::from_path`]. If `None`, no grant expires.
+    #[serde(skip)]
+    pub today: Option<Date>,
 }
 
 impl WorkspaceConfig {
@@ -13,65 +57,556 @@ impl WorkspaceConfig {
         Self {
             rules: vec![CrateRule {
                 caps: [Capability::Alloc, Capability::Panic].into_iter().collect(),
-                crates: vec![CratePattern::Any],
+                crates: vec![CratePattern::any()],
+                targets: vec![],
+                dep_kinds: vec![],
+                features: vec![],
+                reason: None,
+                reviewed_by: None,
+                reviewed_at: None,
+                expires: None,
             }],
+            ..Default::default()
         }
     }
 
-    /// What capabilities has this crate been granted?
-    pub fn crate_caps(&self, crate_name: &CrateName) -> CapabilitySet {
+    /// What capabilities has this artifact been granted?
+    ///
+    /// Expired grants are ignored.
+    pub fn crate_caps(&self, target: &CrateTarget<'_>) -> CapabilitySet {
         let mut caps = CapabilitySet::new();
         for rule in &self.rules {
-            if rule.matches(crate_name) {
+            if !self.is_expired(rule) && rule.matches(target) {
                 caps.extend(rule.caps.iter().copied());
             }
         }
         caps
     }
+
+    pub fn is_expired(&self, rule: &CrateRule) -> bool {
+        rule.expires
+            .is_some_and(|expires| self.today.is_some_and(|today| expires <= today))
+    }
+
+    /// The grants that have expired, and so no longer apply.
+    pub fn expired_rules(&self) -> impl Iterator<Item = &CrateRule> {
+        self.rules.iter().filter(|rule| self.is_expired(rule))
+    }
+
+    /// Which of `caps` are forbidden for this artifact, and by which [`DenyRule`]s?
+    pub fn denials(
+        &self,
+        target: &CrateTarget<'_>,
+        caps: &CapabilitySet,
+    ) -> BTreeMap<Capability, Vec<Denial>> {
+        let mut denials: BTreeMap<Capability, Vec<Denial>> = BTreeMap::new();
+        for rule in &self.deny {
+            if rule.matches(target) {
+                for &cap in caps {
+                    if rule.caps.contains(&Capability::Wildcard)
+                        || rule.caps.iter().any(|denied| denied.overlaps(cap))
+                    {
+                        denials.entry(cap).or_default().push(Denial {
+                            rule: rule.to_string(),
+                            file: rule.file.as_deref().map(|file| file.to_string()),
+                            reason: rule.reason.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        denials
+    }
+
+    /// Find all the packages in the [`DenyRule::trees`],
+    /// i.e. the matching packages and all their dependencies, direct or indirect.
+    pub fn resolve_trees(&mut self, metadata: &Metadata) -> anyhow::Result<()> {
+        let resolve = metadata
+            .resolve
+            .as_ref()
+            .context("cargo metadata has no resolved dependency graph")?;
+        let dependencies: HashMap<&PackageId, &[PackageId]> = resolve
+            .nodes
+            .iter()
+            .map(|node| (&node.id, node.dependencies.as_slice()))
+            .collect();
+
+        for rule in &mut self.deny {
+            let mut queue = vec![];
+            for package in &metadata.packages {
+                let crate_name = CrateName::new(package.name.to_string())?;
+                if rule
+                    .trees
+                    .iter()
+                    .any(|pattern| pattern.matches(&crate_name, &package.version))
+                {
+                    queue.push(&package.id);
+                }
+            }
+
+            while let Some(package_id) = queue.pop() {
+                if rule.tree_packages.insert(package_id.clone()) {
+                    queue.extend(dependencies.get(package_id).copied().unwrap_or_default());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 pub struct CrateRule {
     /// What capabilities are granted?
     pub caps: CapabilitySet,
 
     /// What crates does the rule apply to?
+    ///
+    /// In `[package.metadata.caps]` of a `Cargo.toml`, this defaults to the package itself.
+    #[serde(default)]
     pub crates: Vec<CratePattern>,
+
+    /// What artifacts of the crates does the rule apply to,
+    /// e.g. `["custom-build"]` for only their `build.rs`.
+    ///
+    /// If empty, the rule applies to all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetKind>,
+
+    /// Only apply the rule to crates that are used in these ways,
+    /// e.g. `["dev"]` for crates that are only dev-dependencies.
+    ///
+    /// A crate that is used in several ways, e.g. both as a normal and a build-dependency,
+    /// only matches if all of them are listed.
+    /// If empty, the rule applies regardless of how the crate is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dep_kinds: Vec<DepKind>,
+
+    /// Only apply the rule when all these features of the crate are enabled,
+    /// e.g. `["cookies"]`.
+    ///
+    /// If empty, the rule applies regardless of features.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+
+    /// Why the capabilities were granted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// Who reviewed the crates and approved the grant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewed_by: Option<String>,
+
+    /// When the grant was approved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewed_at: Option<Date>,
+
+    /// The grant no longer applies from this date, and must be reviewed again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<Date>,
+}
+
+impl core::fmt::Display for CrateRule {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "grant of {} to {}",
+            self.caps.iter().join(", "),
+            self.crates.iter().join(", ")
+        )?;
+        if let Some(reviewed_by) = &self.reviewed_by {
+            write!(f, ", reviewed by {reviewed_by}")?;
+        }
+        if let Some(reviewed_at) = &self.reviewed_at {
+            write!(f, " at {reviewed_at}")?;
+        }
+        Ok(())
+    }
 }
 
 impl CrateRule {
-    pub fn matches(&self, crate_name: &CrateName) -> bool {
-        self.crates
-            .iter()
-            .any(|pattern| pattern.matches(crate_name))
+    pub fn matches(&self, target: &CrateTarget<'_>) -> bool {
+        (self.targets.is_empty() || self.targets.contains(target.kind))
+            && (self.dep_kinds.is_empty()
+                // Fail closed: we don't know how a crate without dep-kinds is used.
+                || (!target.dep_kinds.kind.is_empty()
+                    && target
+                        .dep_kinds
+                        .kind
+                        .iter()
+                        .all(|kind| self.dep_kinds.contains(kind))))
+            && self
+                .features
+                .iter()
+                .all(|feature| target.features.contains(feature))
+            && self
+                .crates
+                .iter()
+                .any(|pattern| pattern.matches(target.name, target.version))
+    }
+}
+
+/// Forbids capabilities, overriding any [`CrateRule`] that grants them.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DenyRule {
+    /// What capabilities are forbidden? `"*"` forbids all of them.
+    pub caps: CapabilitySet,
+
+    /// What crates does the rule apply to?
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crates: Vec<CratePattern>,
+
+    /// The rule also applies to these crates, and all their dependencies, direct or indirect.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trees: Vec<CratePattern>,
+
+    /// Crates the rule does NOT apply to, even if they match [`Self::crates`] or [`Self::trees`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub except: Vec<CratePattern>,
+
+    /// Why the capabilities are forbidden. Shown when the rule fires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// All the packages in [`Self::trees`]. Filled in by [`WorkspaceConfig::resolve_trees`].
+    #[serde(skip)]
+    tree_packages: HashSet<PackageId>,
+
+    /// The file the rule was defined in, if any.
+    #[serde(skip)]
+    pub file: Option<Arc<Utf8PathBuf>>,
+}
+
+impl core::fmt::Display for DenyRule {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "denial of {} to {}",
+            self.caps.iter().join(", "),
+            self.crates.iter().chain(&self.trees).join(", ")
+        )?;
+        if !self.except.is_empty() {
+            write!(f, " except {}", self.except.iter().join(", "))?;
+        }
+        Ok(())
     }
 }
 
+impl DenyRule {
+    pub fn matches(&self, target: &CrateTarget<'_>) -> bool {
+        let matches_pattern = |patterns: &[CratePattern]| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches(target.name, target.version))
+        };
+        (matches_pattern(&self.crates) || self.tree_packages.contains(target.package_id))
+            && !matches_pattern(&self.except)
+    }
+}
+
+/// A capability was forbidden by a [`DenyRule`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Denial {
+    /// The [`DenyRule`], e.g. `denial of net to * except reqwest`.
+    pub rule: String,
+
+    /// The file the rule was defined in, if any. See [`DenyRule::file`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    /// See [`DenyRule::reason`].
+    pub reason: Option<String>,
+}
+
+impl core::fmt::Display for Denial {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Self { rule, file, reason } = self;
+        write!(f, "{rule}")?;
+        if let Some(file) = file {
+            write!(f, " in {}", crate::checker::as_relative_path(file.as_ref()))?;
+        }
+        if let Some(reason) = reason {
+            write!(f, " ({reason})")?;
+        }
+        Ok(())
+    }
+}
+
+/// An artifact of a crate, e.g. its library or its `build.rs`,
+/// that we want to know the granted capabilities of.
+#[derive(Clone, Copy, Debug)]
+pub struct CrateTarget<'a> {
+    pub package_id: &'a PackageId,
+    pub name: &'a CrateName,
+    pub version: &'a Version,
+    pub kind: &'a TargetKind,
+
+    /// How the workspace depends on the crate.
+    pub dep_kinds: &'a DepKindSet,
+
+    /// The enabled features of the crate.
+    pub features: &'a [String],
+}
+
 impl WorkspaceConfig {
+    /// Load a config file, and all the files it includes.
     pub fn from_path(path: &Utf8Path) -> anyhow::Result<Self> {
+        let mut config = Self::load_with_includes(path, &mut vec![])?;
+        config.today = Some(Date::today());
+        Ok(config)
+    }
+
+    /// `including` is the chain of files that included this one, used to detect cycles.
+    fn load_with_includes(
+        path: &Utf8Path,
+        including: &mut Vec<Utf8PathBuf>,
+    ) -> anyhow::Result<Self> {
         let file = std::fs::read_to_string(path)
             .map_err(|err| anyhow::format_err!("Failed to load {path:?}: {err}"))?;
-        eon::from_str(&file)
-            .map_err(|err| anyhow::format_err!("Failed to deserialize {path:?}: {err}"))
+        let mut config: Self = eon::from_str(&file)
+            .map_err(|err| anyhow::format_err!("Failed to deserialize {path:?}: {err}"))?;
+        let path_arc = Arc::new(path.to_owned());
+        config.symbol_rules =
+            SymbolRules::from_serialized(&config.symbols, &path_arc, &file, "symbols");
+        config.set_deny_file(&path_arc);
+        config.merge_includes(path, including)
+    }
+
+    /// Load the [`Self::include`]s of the config defined in the file at `path`,
+    /// and add `self` to them.
+    fn merge_includes(
+        mut self,
+        path: &Utf8Path,
+        including: &mut Vec<Utf8PathBuf>,
+    ) -> anyhow::Result<Self> {
+        if self.include.is_empty() {
+            return Ok(self);
+        }
+
+        let canonical = path
+            .canonicalize_utf8()
+            .with_context(|| format!("Failed to canonicalize {path:?}"))?;
+        if let Some(start) = including.iter().position(|p| p == &canonical) {
+            anyhow::bail!(
+                "Config include cycle: {}",
+                including[start..].iter().chain([&canonical]).join(" -> ")
+            );
+        }
+        including.push(canonical);
+
+        let dir = path.parent().unwrap_or_else(|| Utf8Path::new(""));
+        let mut merged = Self::default();
+        for include in core::mem::take(&mut self.include) {
+            let included = Self::load_with_includes(&dir.join(&include), including)
+                .with_context(|| format!("Included from {path:?}"))?;
+            merged.extend(included);
+        }
+        merged.extend(self);
+
+        including.pop();
+        Ok(merged)
+    }
+
+    /// Does the `Cargo.toml` of the workspace, or of any of its members, have a `caps` metadata table?
+    pub fn has_metadata_config(metadata: &Metadata) -> bool {
+        metadata.workspace_metadata.get("caps").is_some()
+            || metadata
+                .workspace_packages()
+                .iter()
+                .any(|package| package.metadata.get("caps").is_some())
+    }
+
+    /// Add the config in `[workspace.metadata.caps]` of the workspace `Cargo.toml`,
+    /// and in `[package.metadata.caps]` of each workspace member.
+    ///
+    /// These are added after the config file, in that order.
+    /// The metadata of packages outside the workspace is ignored:
+    /// a dependency can not grant itself capabilities.
+    pub fn extend_from_metadata(&mut self, metadata: &Metadata) -> anyhow::Result<()> {
+        if let Some(table) = metadata.workspace_metadata.get("caps") {
+            let manifest_path = metadata.workspace_root.join("Cargo.toml");
+            self.extend(Self::from_metadata_table(table, &manifest_path, None)?);
+        }
+        for package in metadata.workspace_packages() {
+            if let Some(table) = package.metadata.get("caps") {
+                let crate_name = CrateName::new(package.name.to_string())?;
+                self.extend(Self::from_metadata_table(
+                    table,
+                    &package.manifest_path,
+                    Some(&crate_name),
+                )?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a `caps` metadata table of the `Cargo.toml` at `manifest_path`.
+    ///
+    /// In the table of a `package`, rules without any `crates` apply to that package.
+    fn from_metadata_table(
+        table: &serde_json::Value,
+        manifest_path: &Utf8Path,
+        package: Option<&CrateName>,
+    ) -> anyhow::Result<Self> {
+        let mut config: Self = serde_json::from_value(table.clone()).with_context(|| {
+            format!("Failed to deserialize the caps metadata in {manifest_path:?}")
+        })?;
+
+        if let Some(crate_name) = package {
+            for rule in &mut config.rules {
+                if rule.crates.is_empty() {
+                    rule.crates.push(CratePattern {
+                        name: NamePattern::Exact(crate_name.clone()),
+                        version: None,
+                    });
+                }
+            }
+        }
+
+        // The line numbers of the symbol rules are only found in eon, not in TOML:
+        let path_arc = Arc::new(manifest_path.to_owned());
+        config.symbol_rules =
+            SymbolRules::from_serialized(&config.symbols, &path_arc, "", "symbols");
+        config.set_deny_file(&path_arc);
+        config.merge_includes(manifest_path, &mut vec![])
     }
+
+    /// Remember that the deny rules were defined in `file`, so we can tell the user where to find them.
+    fn set_deny_file(&mut self, file: &Arc<Utf8PathBuf>) {
+        for rule in &mut self.deny {
+            rule.file = Some(Arc::clone(file));
+        }
+    }
+
+    /// Add the rules of `other`, which takes precedence over `self`.
+    fn extend(&mut self, other: Self) {
+        let Self {
+            include,
+            rules,
+            deny,
+            symbols,
+            symbol_rules,
+            today: _,
+        } = other;
+
+        self.include.extend(include);
+        self.rules.extend(rules);
+        self.deny.extend(deny);
+
+        // The first of equally specific symbol rules wins, so `other` goes first:
+        self.symbols.splice(0..0, symbols);
+        self.symbol_rules.rules.splice(0..0, symbol_rules.rules);
+    }
+}
+
+/// Matches some crates, e.g. `"tokio"`, `"aws_sdk_*"` or `"tokio@^1.40"`.
+pub struct CratePattern {
+    pub name: NamePattern,
+
+    /// If set, only these versions of the crate match,
+    /// e.g. `"tokio@^1.40"` or `"openssl@=0.10.66"`.
+    pub version: Option<VersionReq>,
 }
 
-pub enum CratePattern {
-    /// Matches any crate
+/// Matches the name of a crate.
+pub enum NamePattern {
+    /// `"*"`: matches any crate
     Any,
 
-    /// Matches a specific crate
-    // TODO: certain version
-    Specific(CrateName),
+    /// Matches a specific crate, e.g. `"tokio"`
+    Exact(CrateName),
+
+    /// A glob, e.g. `"aws_sdk_*"`, where `*` matches any number of characters and `?` matches one.
+    Glob { glob: String, regex: regex::Regex },
+
+    /// A regex between slashes, e.g. `"/^windows_(sys|targets)$/"`.
+    Regex(regex::Regex),
 }
 
 impl CratePattern {
+    pub fn any() -> Self {
+        Self {
+            name: NamePattern::Any,
+            version: None,
+        }
+    }
+
+    pub fn matches(&self, crate_name: &CrateName, crate_version: &Version) -> bool {
+        self.name.matches(crate_name)
+            && self
+                .version
+                .as_ref()
+                .is_none_or(|req| req.matches(crate_version))
+    }
+}
+
+impl NamePattern {
+    /// Crate names are always compared in their `snake_case` form,
+    /// so `"aws-sdk-*"` and `"aws_sdk_*"` are the same glob.
     pub fn matches(&self, crate_name: &CrateName) -> bool {
         match self {
             Self::Any => true,
-            Self::Specific(name) => name == crate_name,
+            Self::Exact(name) => name == crate_name,
+            Self::Glob { regex, .. } | Self::Regex(regex) => regex.is_match(crate_name),
         }
     }
+
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        if s == "*" {
+            Ok(Self::Any)
+        } else if let Some(regex) = s
+            .strip_prefix('/')
+            .and_then(|s| s.strip_suffix('/'))
+            .filter(|regex| !regex.is_empty())
+        {
+            let regex = regex::Regex::new(regex)
+                .map_err(|err| anyhow::format_err!("Bad regex in crate pattern {s:?}: {err}"))?;
+            Ok(Self::Regex(regex))
+        } else if s.contains(['*', '?']) {
+            let glob = s.replace('-', "_");
+            CrateName::new(glob.replace(['*', '?'], ""))
+                .map_err(|err| anyhow::format_err!("Bad glob {s:?}: {err}"))?;
+            let regex = glob
+                .split('*')
+                .map(|part| part.split('?').map(regex::escape).join("."))
+                .join(".*");
+            Ok(Self::Glob {
+                regex: regex::Regex::new(&format!("^{regex}$"))?,
+                glob,
+            })
+        } else {
+            Ok(Self::Exact(CrateName::new(s)?))
+        }
+    }
+}
+
+impl core::fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Any => write!(f, "*"),
+            Self::Exact(name) => write!(f, "{name}"),
+            Self::Glob { glob, .. } => write!(f, "{glob}"),
+            Self::Regex(regex) => write!(f, "/{regex}/"),
+        }
+    }
+}
+
+impl core::fmt::Display for CratePattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{version}")?;
+        }
+        Ok(())
+    }
+}
+
+impl serde::Serialize for CratePattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for CratePattern {
@@ -80,11 +615,439 @@ impl<'de> serde::Deserialize<'de> for CratePattern {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        if s == "*" {
-            Ok(Self::Any)
+
+        // A regex may contain an `@`, so only look for a version after it.
+        let version_start = if s.starts_with('/') {
+            s.rfind('/').unwrap_or_default()
         } else {
-            let crate_name = CrateName::new(s).map_err(serde::de::Error::custom)?;
-            Ok(Self::Specific(crate_name))
-        }
+            0
+        };
+        let (name, version) = match s[version_start..].find('@') {
+            Some(at) => {
+                let (name, version) = s.split_at(version_start + at);
+                let version = VersionReq::parse(&version[1..]).map_err(|err| {
+                    serde::de::Error::custom(format!("Bad version in crate pattern {s:?}: {err}"))
+                })?;
+                (name, Some(version))
+            }
+            None => (s.as_str(), None),
+        };
+        let name = NamePattern::parse(name).map_err(serde::de::Error::custom)?;
+        Ok(Self { name, version })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Capability, checker::CheckedArtifact};
+
+    use super::*;
+
+    fn caps_of(config: &WorkspaceConfig, name: &str, version: &str) -> CapabilitySet {
+        caps_of_target(config, name, version, &TargetKind::Lib)
+    }
+
+    fn caps_of_target(
+        config: &WorkspaceConfig,
+        name: &str,
+        version: &str,
+        kind: &TargetKind,
+    ) -> CapabilitySet {
+        let mut target = CheckedArtifact::test_lib(name, &[]);
+        target.version = Version::parse(version).unwrap();
+        target.target_kind = kind.clone();
+        config.crate_caps(&target.crate_target())
+    }
+
+    #[test]
+    fn test_version_constraints() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["net"], crates: ["tokio@^1.40"] }
+                { caps: ["fs"], crates: ["openssl@=0.10.66"] }
+                { caps: ["time"], crates: ["tokio"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let net_and_time = [Capability::Net, Capability::Time].into_iter().collect();
+        assert_eq!(caps_of(&config, "tokio", "1.47.1"), net_and_time);
+        assert_eq!(
+            caps_of(&config, "tokio", "2.0.0"),
+            core::iter::once(Capability::Time).collect(),
+            "A grant for one major version should not extend to the next"
+        );
+        assert_eq!(
+            caps_of(&config, "openssl", "0.10.66"),
+            core::iter::once(Capability::FS).collect()
+        );
+        assert!(caps_of(&config, "openssl", "0.10.67").is_empty());
+
+        assert!(
+            eon::from_str::<WorkspaceConfig>(r#"rules: [{ caps: [], crates: ["tokio@nope"] }]"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_glob_and_regex() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["alloc"], crates: ["*"] }
+                { caps: ["net"], crates: ["aws-sdk-*"] }
+                { caps: ["fs"], crates: ["acme_*@>=2"] }
+                { caps: ["unsafe"], crates: ["/^windows_(sys|targets)$/"] }
+                { caps: ["time"], crates: ["acme_?"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let caps = |caps: &[Capability]| caps.iter().copied().collect::<CapabilitySet>();
+
+        assert_eq!(
+            caps_of(&config, "aws_sdk_s3", "1.0.0"),
+            caps(&[Capability::Alloc, Capability::Net])
+        );
+        assert_eq!(
+            caps_of(&config, "acme_db", "2.1.0"),
+            caps(&[Capability::Alloc, Capability::FS])
+        );
+        assert_eq!(
+            caps_of(&config, "acme_db", "1.9.0"),
+            caps(&[Capability::Alloc])
+        );
+        assert_eq!(
+            caps_of(&config, "acme_x", "2.0.0"),
+            caps(&[Capability::Alloc, Capability::FS, Capability::Time]),
+            "When several rules match, the crate gets the union of their capabilities"
+        );
+        assert_eq!(
+            caps_of(&config, "windows_sys", "0.59.0"),
+            caps(&[Capability::Alloc, Capability::Unsafe])
+        );
+        assert_eq!(
+            caps_of(&config, "windows_sys_extra", "0.59.0"),
+            caps(&[Capability::Alloc])
+        );
+        assert_eq!(
+            caps_of(&config, "aws", "1.0.0"),
+            caps(&[Capability::Alloc]),
+            "A glob must match the whole name"
+        );
+    }
+
+    #[test]
+    fn test_targets() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["alloc"], crates: ["*"] }
+                { caps: ["command", "fs"], crates: ["openssl-sys"], targets: ["custom-build"] }
+                { caps: ["unsafe"], crates: ["openssl-sys"], targets: ["lib", "proc-macro"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let caps = |caps: &[Capability]| caps.iter().copied().collect::<CapabilitySet>();
+
+        assert_eq!(
+            caps_of_target(&config, "openssl-sys", "0.9.0", &TargetKind::CustomBuild),
+            caps(&[Capability::Alloc, Capability::Command, Capability::FS])
+        );
+        assert_eq!(
+            caps_of_target(&config, "openssl-sys", "0.9.0", &TargetKind::Lib),
+            caps(&[Capability::Alloc, Capability::Unsafe])
+        );
+        assert_eq!(
+            caps_of_target(&config, "openssl-sys", "0.9.0", &TargetKind::Bin),
+            caps(&[Capability::Alloc])
+        );
+    }
+
+    #[test]
+    fn test_dep_kinds() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["alloc"], crates: ["*"] }
+                { caps: ["*"], crates: ["*"], dep_kinds: ["dev"] }
+                { caps: ["fs"], crates: ["*"], dep_kinds: ["build", "proc-macro"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let caps_of_kinds = |kinds: &[DepKind]| {
+            let mut target = CheckedArtifact::test_lib("cc", &[]);
+            target.dep_kinds = DepKindSet {
+                kind: kinds.iter().copied().collect(),
+            };
+            config.crate_caps(&target.crate_target())
+        };
+        let caps = |caps: &[Capability]| caps.iter().copied().collect::<CapabilitySet>();
+
+        assert_eq!(
+            caps_of_kinds(&[DepKind::Dev]),
+            caps(&[Capability::Alloc, Capability::Wildcard])
+        );
+        assert_eq!(
+            caps_of_kinds(&[DepKind::Build]),
+            caps(&[Capability::Alloc, Capability::FS])
+        );
+        assert_eq!(
+            caps_of_kinds(&[DepKind::Build, DepKind::ProcMacro]),
+            caps(&[Capability::Alloc, Capability::FS])
+        );
+        assert_eq!(
+            caps_of_kinds(&[DepKind::Build, DepKind::Normal]),
+            caps(&[Capability::Alloc]),
+            "A crate that is also used at runtime should not get the build-time grants"
+        );
+        assert_eq!(
+            caps_of_kinds(&[]),
+            caps(&[Capability::Alloc]),
+            "We don't know how a crate without dep-kinds is used, so it should get no dep-kind grants"
+        );
+    }
+
+    #[test]
+    fn test_deny() {
+        let mut config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["net", "command"], crates: ["*"] }
+            ]
+            deny: [
+                { caps: ["net"], crates: ["*"], except: ["reqwest"], reason: "Only reqwest may talk to the network" }
+                { caps: ["command"], trees: ["acme_core"] }
+            ]
+            "#,
+        )
+        .unwrap();
+        config.deny[1].tree_packages = ["acme_core", "acme_util"]
+            .into_iter()
+            .map(|name| PackageId {
+                repr: name.to_owned(),
+            })
+            .collect();
+
+        let denials = |name: &str| {
+            let target = CheckedArtifact::test_lib(name, &[]);
+            let target = target.crate_target();
+            let caps = config.crate_caps(&target);
+            config
+                .denials(&target, &caps)
+                .into_iter()
+                .map(|(cap, denials)| format!("{cap}: {}", denials.iter().join(", ")))
+                .collect_vec()
+        };
+
+        assert!(denials("reqwest").is_empty());
+        assert_eq!(
+            denials("hyper"),
+            ["net: denial of net to * except reqwest (Only reqwest may talk to the network)"]
+        );
+        assert_eq!(
+            denials("acme_util"),
+            [
+                "net: denial of net to * except reqwest (Only reqwest may talk to the network)",
+                "command: denial of command to acme_core",
+            ]
+        );
+
+        config.deny = eon::from_str(r#"[{ caps: ["fs.write"], crates: ["*"] }]"#).unwrap();
+        let fs = [Capability::FS, Capability::FsRead, Capability::FsWrite]
+            .into_iter()
+            .collect();
+        let serde_json = CheckedArtifact::test_lib("serde_json", &[]);
+        assert_eq!(
+            config
+                .denials(&serde_json.crate_target(), &fs)
+                .keys()
+                .copied()
+                .collect_vec(),
+            [Capability::FS, Capability::FsWrite],
+            "Denying fs.write should also deny an fs that could be a write"
+        );
+    }
+
+    #[test]
+    fn test_include() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        std::fs::create_dir_all(dir.join("policy")).unwrap();
+        std::fs::write(
+            dir.join("policy/base.eon"),
+            r#"
+            rules: [{ caps: ["alloc"], crates: ["*"] }]
+            deny: [{ caps: ["net"], crates: ["*"], except: ["reqwest"] }]
+            symbols: [{ caps: ["fs"], patterns: ["acme_open"] }]
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("cargo-caps.eon"),
+            r#"
+            include: ["policy/base.eon"]
+            rules: [{ caps: ["time"], crates: ["tokio@^1.40", "aws-sdk-*"] }]
+            symbols: [{ caps: ["net"], patterns: ["acme_open"] }]
+            "#,
+        )
+        .unwrap();
+
+        let config = WorkspaceConfig::from_path(&dir.join("cargo-caps.eon")).unwrap();
+        assert!(config.include.is_empty());
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.deny.len(), 1);
+        assert_eq!(
+            config.deny[0].file.as_deref(),
+            Some(&dir.join("policy/base.eon")),
+            "Denials should point at the file the user needs to look in"
+        );
+        assert_eq!(
+            caps_of(&config, "tokio", "1.47.0"),
+            [Capability::Alloc, Capability::Time].into_iter().collect()
+        );
+        let rules = SymbolRules::load(&config);
+        assert_eq!(
+            rules.match_symbol("acme_open").unwrap().caps,
+            &core::iter::once(Capability::Net).collect(),
+            "The including file should win over the included one"
+        );
+
+        let printed = eon::to_string(&config, &Default::default()).unwrap();
+        assert!(printed.contains(r#""tokio@^1.40""#), "{printed}");
+        assert!(printed.contains(r#""aws_sdk_*""#), "{printed}");
+        let reparsed: WorkspaceConfig = eon::from_str(&printed).unwrap();
+        assert_eq!(reparsed.rules.len(), 2);
+
+        // Cycles are an error:
+        std::fs::write(
+            dir.join("policy/base.eon"),
+            r#"include: ["../cargo-caps.eon"]"#,
+        )
+        .unwrap();
+        let Err(err) = WorkspaceConfig::from_path(&dir.join("cargo-caps.eon")) else {
+            panic!("Expected an include cycle error");
+        };
+        assert!(format!("{err:#}").contains("include cycle"), "{err:#}");
+    }
+
+    #[test]
+    fn test_metadata_config() {
+        let workspace = WorkspaceConfig::from_metadata_table(
+            &serde_json::json!({
+                "rules": [{ "caps": ["net"], "crates": ["reqwest"] }],
+                "symbols": [{ "caps": ["fs"], "patterns": ["acme_open"] }],
+            }),
+            Utf8Path::new("Cargo.toml"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            caps_of(&workspace, "reqwest", "0.12.0"),
+            core::iter::once(Capability::Net).collect()
+        );
+        assert!(
+            SymbolRules::load(&workspace)
+                .match_symbol("acme_open")
+                .is_some()
+        );
+
+        let my_app = CrateName::new("my-app").unwrap();
+        let package = WorkspaceConfig::from_metadata_table(
+            &serde_json::json!({
+                "rules": [
+                    { "caps": ["fs"] },
+                    { "caps": ["time"], "crates": ["tokio"] },
+                ],
+            }),
+            Utf8Path::new("my-app/Cargo.toml"),
+            Some(&my_app),
+        )
+        .unwrap();
+        assert_eq!(
+            caps_of(&package, "my_app", "1.0.0"),
+            core::iter::once(Capability::FS).collect(),
+            "Rules without crates should apply to the package itself"
+        );
+        assert_eq!(
+            caps_of(&package, "tokio", "1.0.0"),
+            core::iter::once(Capability::Time).collect()
+        );
+    }
+
+    #[test]
+    fn test_expires() {
+        let mut config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                {
+                    caps: ["net"]
+                    crates: ["reqwest"]
+                    reason: "HTTP client"
+                    reviewed_by: "alice"
+                    reviewed_at: "2025-01-10"
+                    expires: "2025-07-01"
+                }
+                { caps: ["alloc"], crates: ["*"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let net_and_alloc = [Capability::Alloc, Capability::Net].into_iter().collect();
+        assert_eq!(caps_of(&config, "reqwest", "0.12.0"), net_and_alloc);
+
+        config.today = Some("2025-06-30".parse().unwrap());
+        assert_eq!(caps_of(&config, "reqwest", "0.12.0"), net_and_alloc);
+        assert_eq!(config.expired_rules().count(), 0);
+
+        config.today = Some("2025-07-01".parse().unwrap());
+        assert_eq!(
+            caps_of(&config, "reqwest", "0.12.0"),
+            core::iter::once(Capability::Alloc).collect()
+        );
+        let expired = config
+            .expired_rules()
+            .map(ToString::to_string)
+            .collect_vec();
+        assert_eq!(
+            expired,
+            ["grant of net to reqwest, reviewed by alice at 2025-01-10"]
+        );
+    }
+
+    #[test]
+    fn test_features() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["net"], crates: ["reqwest"] }
+                { caps: ["fs"], crates: ["reqwest"], features: ["cookies"] }
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let caps_with_features = |features: &[&str]| {
+            let mut target = CheckedArtifact::test_lib("reqwest", &[]);
+            target.features = features.iter().map(ToString::to_string).collect();
+            config.crate_caps(&target.crate_target())
+        };
+
+        assert_eq!(
+            caps_with_features(&["default", "json"]),
+            core::iter::once(Capability::Net).collect()
+        );
+        assert_eq!(
+            caps_with_features(&["cookies", "default"]),
+            [Capability::Net, Capability::FS].into_iter().collect()
+        );
     }
 }
@@ -0,0 +1,215 @@
+//! `cargo-caps config check`: find problems in the workspace config,
+//! e.g. rules for crates we no longer depend on.
+
+use std::collections::HashMap;
+
+use cargo_metadata::semver::Version;
+
+use crate::{
+    Capability, CrateName,
+    checker::CheckerOutput,
+    config::{CratePattern, WorkspaceConfig},
+};
+
+/// How bad is a [`Finding`]?
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Worth knowing about, but not necessarily wrong.
+    Note,
+
+    /// Most likely a mistake, or a rule that has rotted.
+    Warning,
+}
+
+/// A problem with the workspace config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl core::fmt::Display for Finding {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Self { severity, message } = self;
+        match severity {
+            Severity::Note => write!(f, "note: {message}"),
+            Severity::Warning => write!(f, "warning: {message}"),
+        }
+    }
+}
+
+/// Check the config against the packages in the dependency graph.
+///
+/// If we have the `output` of a build, we also look for over-grants:
+/// rules that grant capabilities no matching crate has.
+pub fn check_config(
+    config: &WorkspaceConfig,
+    packages: &[(CrateName, Version)],
+    output: Option<&CheckerOutput>,
+) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut warn = |message: String| {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            message,
+        });
+    };
+
+    let is_unknown = |pattern: &CratePattern| {
+        !packages
+            .iter()
+            .any(|(name, version)| pattern.matches(name, version))
+    };
+
+    let mut seen_rules: HashMap<String, usize> = HashMap::new();
+
+    for (rule_index, rule) in config.rules.iter().enumerate() {
+        let label = format!("rule #{rule_index} ({rule})");
+
+        for pattern in rule.crates.iter().filter(|pattern| is_unknown(pattern)) {
+            warn(format!(
+                "{label}: `{pattern}` does not match any crate in the dependency graph"
+            ));
+        }
+
+        // Two rules are duplicates if they grant the same thing, regardless of audit metadata.
+        let key = serde_json::to_string(&(
+            &rule.caps,
+            &rule.crates,
+            &rule.targets,
+            &rule.dep_kinds,
+            &rule.features,
+        ))
+        .unwrap_or_default();
+        if let Some(first) = seen_rules.insert(key, rule_index) {
+            warn(format!("{label} is a duplicate of rule #{first}"));
+        }
+
+        if let Some(output) = output
+            && !config.is_expired(rule)
+        {
+            let matching = output
+                .checked
+                .iter()
+                .filter(|checked| rule.matches(&checked.crate_target()))
+                .collect::<Vec<_>>();
+            if !matching.is_empty() {
+                for &cap in &rule.caps {
+                    let is_observed = cap == Capability::Wildcard
+                        || matching
+                            .iter()
+                            .flat_map(|checked| checked.caps.caps.keys())
+                            .any(|observed| cap.overlaps(*observed));
+                    if !is_observed {
+                        warn(format!(
+                            "{label} grants `{cap}`, but none of the matching crates have it"
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    for (rule_index, rule) in config.deny.iter().enumerate() {
+        for pattern in rule
+            .crates
+            .iter()
+            .chain(&rule.trees)
+            .chain(&rule.except)
+            .filter(|pattern| is_unknown(pattern))
+        {
+            warn(format!(
+                "deny rule #{rule_index}: `{pattern}` does not match any crate in the dependency graph"
+            ));
+        }
+    }
+
+    for (rule_index, rule) in config.rules.iter().enumerate() {
+        for cap in rule.caps.iter().filter(|cap| cap.is_critical()) {
+            findings.push(Finding {
+                severity: Severity::Note,
+                message: format!(
+                    "rule #{rule_index} ({rule}) grants the critical capability `{cap}`"
+                ),
+            });
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::CheckedArtifact;
+
+    use super::*;
+
+    #[test]
+    fn test_check_config() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["alloc"], crates: ["*"] }
+                { caps: ["net"], crates: ["reqwest", "hyper@^0.14"] }
+                { caps: ["net"], crates: ["reqwest", "hyper@^0.14"], reason: "Same thing" }
+                { caps: ["unsafe"], crates: ["libc"] }
+            ]
+            deny: [
+                { caps: ["command"], trees: ["acme_core"] }
+            ]
+            "#,
+        )
+        .unwrap();
+        let packages = [
+            (CrateName::new("reqwest").unwrap(), Version::new(0, 12, 0)),
+            (CrateName::new("hyper").unwrap(), Version::new(1, 0, 0)),
+            (CrateName::new("libc").unwrap(), Version::new(0, 2, 0)),
+        ];
+
+        let findings = check_config(&config, &packages, None)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                "warning: rule #1 (grant of net to reqwest, hyper@^0.14): `hyper@^0.14` does not match any crate in the dependency graph",
+                "warning: rule #2 (grant of net to reqwest, hyper@^0.14): `hyper@^0.14` does not match any crate in the dependency graph",
+                "warning: rule #2 (grant of net to reqwest, hyper@^0.14) is a duplicate of rule #1",
+                "warning: deny rule #0: `acme_core` does not match any crate in the dependency graph",
+                "note: rule #3 (grant of unsafe to libc) grants the critical capability `unsafe`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_over_grants() {
+        let config: WorkspaceConfig = eon::from_str(
+            r#"
+            rules: [
+                { caps: ["net", "fs"], crates: ["reqwest"] }
+                { caps: ["*"], crates: ["reqwest"] }
+            ]
+            "#,
+        )
+        .unwrap();
+        let reqwest = CheckedArtifact::test_lib("reqwest", &[Capability::Net]);
+        let packages = [(reqwest.crate_name.clone(), reqwest.version.clone())];
+        let output = CheckerOutput {
+            checked: vec![reqwest],
+            ..Default::default()
+        };
+
+        let findings = check_config(&config, &packages, Some(&output))
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                "warning: rule #0 (grant of net, fs to reqwest) grants `fs`, but none of the matching crates have it",
+                "note: rule #1 (grant of * to reqwest) grants the critical capability `*`",
+            ]
+        );
+    }
+}
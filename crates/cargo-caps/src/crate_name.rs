@@ -45,6 +45,19 @@ impl core::fmt::Display for CrateName {
     }
 }
 
+impl serde::Serialize for CrateName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CrateName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::new(s).map_err(serde::de::Error::custom)
+    }
+}
+
 impl AsRef<str> for CrateName {
     fn as_ref(&self) -> &str {
         &self.0
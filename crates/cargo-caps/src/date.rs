@@ -0,0 +1,106 @@
+/// A calendar date, written as `YYYY-MM-DD`, e.g. `2025-03-14`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Today's date in UTC.
+    pub fn today() -> Self {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Self::from_days_since_epoch((seconds / 86_400) as i64)
+    }
+
+    /// Convert the number of days since 1970-01-01 into a date.
+    ///
+    /// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    #[expect(clippy::cast_possible_truncation)]
+    fn from_days_since_epoch(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+}
+
+impl core::fmt::Display for Date {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Self { year, month, day } = self;
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl core::str::FromStr for Date {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || -> Option<Self> {
+            let mut parts = s.split('-');
+            let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+            if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+                return None;
+            }
+            let date = Self {
+                year: year.parse().ok()?,
+                month: month.parse().ok()?,
+                day: day.parse().ok()?,
+            };
+            ((1..=12).contains(&date.month) && (1..=31).contains(&date.day)).then_some(date)
+        };
+        parse().ok_or_else(|| anyhow::format_err!("Expected a date like 2025-03-14, got {s:?}"))
+    }
+}
+
+impl serde::Serialize for Date {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Date {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn test_date() {
+    let date = |s: &str| s.parse::<Date>().unwrap();
+
+    assert_eq!(Date::from_days_since_epoch(0), date("1970-01-01"));
+    assert_eq!(Date::from_days_since_epoch(19_797), date("2024-03-15"));
+    assert_eq!(Date::from_days_since_epoch(19_782), date("2024-02-29"));
+    assert_eq!(date("2024-03-15").to_string(), "2024-03-15");
+    assert!(date("2024-12-31") < date("2025-01-01"));
+
+    assert!("2024-3-15".parse::<Date>().is_err());
+    assert!("2024-13-01".parse::<Date>().is_err());
+    assert!("yesterday".parse::<Date>().is_err());
+}
@@ -5,7 +5,8 @@ pub fn demangle_symbol(name: &str) -> String {
     {
         decode_rust_type(&demangled)
     } else if let Ok(demangled) = rustc_demangle::try_demangle(name) {
-        decode_rust_type(&demangled.to_string())
+        // The alternate format leaves out the hashes, e.g. `std[1a2b3c4d5e6f7a8b]` in v0 symbols.
+        simplify_inherent_impls(&decode_rust_type(&format!("{demangled:#}")))
     } else if let Some(manual_demangled) = try_manual_demangle(name) {
         decode_rust_type(&manual_demangled)
     } else {
@@ -13,7 +14,9 @@ pub fn demangle_symbol(name: &str) -> String {
     };
 
     // Some function names ends with e.g. ::hdfea6b6d53cc7e8c - strip that:
-    if let Some(hash_pos) = demangled.rfind("::h") {
+    if let Some(hash_pos) = demangled.rfind("::h")
+        && is_hash(&demangled[hash_pos + 3..])
+    {
         demangled = demangled[..hash_pos].to_owned();
     }
 
@@ -22,6 +25,28 @@ pub fn demangle_symbol(name: &str) -> String {
     demangled
 }
 
+/// Is this the 16 hex digits of a legacy symbol hash?
+///
+/// Careful not to mistake e.g. the `hir` of `regex_syntax::hir::Hir` for a hash.
+fn is_hash(s: &str) -> bool {
+    s.len() == 16 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// v0 symbols write inherent methods as `<std::fs::File>::open`.
+/// Turn that into `std::fs::File::open`, like in legacy symbols, so that rules can match them.
+fn simplify_inherent_impls(demangled: &str) -> String {
+    use std::sync::LazyLock;
+
+    static INHERENT_IMPL_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"<([A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)*)>::")
+            .expect("Invalid regex")
+    });
+
+    INHERENT_IMPL_REGEX
+        .replace_all(demangled, "$1::")
+        .into_owned()
+}
+
 fn decode_rust_type(mut encoded: &str) -> String {
     // Find things like `$LT,GT$"` and decode into `<>`:
     let mut decoded = String::new();
@@ -179,6 +204,42 @@ mod tests {
         assert_eq!(decode_rust_type("normal_text"), "normal_text");
     }
 
+    #[test]
+    fn test_demangle_v0() {
+        assert_eq!(
+            demangle_symbol("_RNvMsj_NtCsgczF5crJ4sT_3std2fsNtB5_11OpenOptions5__open"),
+            "std::fs::OpenOptions::_open"
+        );
+        assert_eq!(
+            demangle_symbol(
+                "_RNvNvMs7_NtNtNtNtCsgczF5crJ4sT_3std3sys3net10connection6socketNtB7_11TcpListener4bind5inner"
+            ),
+            "std::sys::net::connection::socket::TcpListener::bind::inner"
+        );
+        assert_eq!(
+            demangle_symbol(
+                "_RNvXs6_NtNtCsgczF5crJ4sT_3std3net11socket_addreNtB5_13ToSocketAddrs15to_socket_addrs"
+            ),
+            "<str as std::net::socket_addr::ToSocketAddrs>::to_socket_addrs"
+        );
+        assert_eq!(
+            demangle_symbol("_RNvCs9hJ03s5DiqP_7___rustc14___rust_dealloc"),
+            "rustc::__rust_dealloc"
+        );
+        assert_eq!(
+            demangle_symbol(
+                "_RNvNtCscI6d9CVNmLh_4core4hint21unreachable_uncheckedCsh0ZdAyRBdLY_12aho_corasick"
+            ),
+            "core::hint::unreachable_unchecked"
+        );
+        assert_eq!(
+            demangle_symbol(
+                "_RINvMs3_NtCs7aH2Im2aPEx_12regex_syntax3hirNtB6_3Hir7literalINtNtCs40k4W9msRzi_5alloc3vec3VechEEB8_"
+            ),
+            "regex_syntax::hir::Hir::literal::<alloc::vec::Vec<u8>>"
+        );
+    }
+
     #[test]
     fn test_demangle() {
         assert_eq!(
@@ -0,0 +1,220 @@
+//! [Graphviz](https://graphviz.org/) DOT output of the dependency graph,
+//! showing how capabilities propagate through it.
+//!
+//! Each checked package becomes a node, colored by its deduced capabilities.
+//! Packages that have a capability because of their own code or symbols
+//! (rather than inheriting it from a dependency) get a double border,
+//! and the edges along which capabilities were inherited are drawn in red.
+//!
+//! Render it with e.g. `cargo-caps check --format dot | dot -Tsvg > caps.svg`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cargo_metadata::{PackageId, semver::Version};
+use itertools::Itertools as _;
+
+use crate::{
+    Capability, CrateName,
+    build_graph_analysis::DepGraph,
+    capability::{CapabilitySet, Reason},
+    checker::CheckerOutput,
+};
+
+/// A package in the graph, merged from all its checked artifacts.
+struct Node<'a> {
+    crate_name: &'a CrateName,
+    version: &'a Version,
+
+    /// The capabilities of the package, and whether or not
+    /// it has it for some other reason than a dependency.
+    caps: BTreeMap<Capability, bool>,
+}
+
+pub fn dot_graph(graph: &DepGraph, output: &CheckerOutput) -> String {
+    use core::fmt::Write as _;
+
+    let mut nodes: BTreeMap<&PackageId, Node<'_>> = BTreeMap::new();
+    for checked in &output.checked {
+        let node = nodes.entry(&checked.package_id).or_insert_with(|| Node {
+            crate_name: &checked.crate_name,
+            version: &checked.version,
+            caps: BTreeMap::new(),
+        });
+        for (&cap, reasons) in &checked.caps.caps {
+            let is_direct = reasons
+                .iter()
+                .any(|reason| !matches!(reason, Reason::Crate(_)));
+            *node.caps.entry(cap).or_default() |= is_direct;
+        }
+    }
+
+    let mut dot = String::new();
+    dot.push_str("digraph cargo_caps {\n");
+    dot.push_str("    node [shape=box, style=filled];\n");
+
+    for (package_id, node) in &nodes {
+        let caps = node
+            .caps
+            .keys()
+            .map(|cap| format!("{}{cap}", cap.emoji().trim()))
+            .join(" ");
+        let introduces_caps = node
+            .caps
+            .iter()
+            .any(|(cap, &is_direct)| is_direct && !is_basic(*cap));
+        writeln!(
+            dot,
+            "    {} [label={}, fillcolor={:?}, peripheries={}];",
+            quoted(&package_id.repr),
+            quoted(&format!("{} {}\n{caps}", node.crate_name, node.version)),
+            fill_color(node.caps.keys().copied()),
+            if introduces_caps { 2 } else { 1 },
+        )
+        .ok();
+    }
+
+    let mut edges = BTreeSet::new();
+    for (dependent, dependency, _) in graph.dependencies() {
+        // Skip packages that were not built, e.g. platform-specific dependencies
+        if !nodes.contains_key(dependent) {
+            continue;
+        }
+        let Some(dependency_node) = nodes.get(dependency) else {
+            continue;
+        };
+        if edges.insert((dependent, dependency)) {
+            let inherited = inherited_caps(output, dependent, dependency_node.crate_name);
+            let attributes = if inherited.is_empty() {
+                r#"color="gray""#.to_owned()
+            } else {
+                let label = inherited
+                    .iter()
+                    .map(|cap| format!("{}{cap}", cap.emoji().trim()))
+                    .join(" ");
+                format!(r#"color="red", penwidth=2, label={}"#, quoted(&label))
+            };
+            writeln!(
+                dot,
+                "    {} -> {} [{attributes}];",
+                quoted(&dependent.repr),
+                quoted(&dependency.repr),
+            )
+            .ok();
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// The capabilities `dependent` has because it depends on `dependency`.
+fn inherited_caps(
+    output: &CheckerOutput,
+    dependent: &PackageId,
+    dependency: &CrateName,
+) -> CapabilitySet {
+    output
+        .checked
+        .iter()
+        .filter(|checked| &checked.package_id == dependent)
+        .flat_map(|checked| &checked.caps.caps)
+        .filter(|(_, reasons)| reasons.contains(&Reason::Crate(dependency.clone())))
+        .map(|(&cap, _)| cap)
+        .collect()
+}
+
+/// Almost every crate has these, so they are not very interesting.
+fn is_basic(cap: Capability) -> bool {
+    matches!(cap, Capability::Alloc | Capability::Panic)
+}
+
+fn fill_color(caps: impl Iterator<Item = Capability>) -> &'static str {
+    let caps: CapabilitySet = caps.collect();
+    if caps.iter().any(|cap| cap.is_critical()) {
+        "#ffb3b3" // red
+    } else if caps.iter().any(|cap| {
+        matches!(
+            cap.umbrella().unwrap_or(*cap),
+            Capability::Net | Capability::FS
+        )
+    }) {
+        "#ffd699" // orange
+    } else if caps.iter().any(|&cap| !is_basic(cap)) {
+        "#ffffcc" // yellow
+    } else {
+        "white"
+    }
+}
+
+/// A DOT string literal.
+fn quoted(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{build_graph_analysis::DepKind, checker::CheckedArtifact};
+
+    use super::*;
+
+    #[test]
+    fn test_dot_graph() {
+        let mut my_app = CheckedArtifact::test_lib("my_app", &[]);
+        my_app.caps.caps.insert(
+            Capability::Net,
+            core::iter::once(Reason::Crate(CrateName::new("hyper").unwrap())).collect(),
+        );
+        let output = CheckerOutput {
+            checked: vec![
+                CheckedArtifact::test_lib("hyper", &[Capability::Net]),
+                my_app,
+                CheckedArtifact::test_lib("itoa", &[]),
+            ],
+            ..Default::default()
+        };
+
+        let mut graph = DepGraph::default();
+        for (dependent, dependency) in [("my_app", "hyper"), ("my_app", "itoa"), ("itoa", "libc")] {
+            graph.add_edge(
+                PackageId {
+                    repr: dependent.to_owned(),
+                },
+                PackageId {
+                    repr: dependency.to_owned(),
+                },
+                DepKind::Normal.into(),
+            );
+        }
+
+        let dot = dot_graph(&graph, &output);
+        let lines: BTreeMap<&str, &str> = dot
+            .lines()
+            .filter_map(|line| line.trim().split_once(" ["))
+            .collect();
+
+        assert_eq!(
+            lines[r#""hyper""#],
+            r##"label="hyper 1.0.0\n🌐net", fillcolor="#ffd699", peripheries=2];"##
+        );
+        assert_eq!(
+            lines[r#""my_app""#],
+            r##"label="my_app 1.0.0\n🌐net", fillcolor="#ffd699", peripheries=1];"##,
+            "my_app only inherits net, so it should not be marked as introducing it"
+        );
+        assert_eq!(
+            lines[r#""my_app" -> "hyper""#],
+            r#"color="red", penwidth=2, label="🌐net"];"#
+        );
+        assert_eq!(lines[r#""my_app" -> "itoa""#], r#"color="gray"];"#);
+        assert!(
+            !dot.contains("libc"),
+            "Packages that were not checked should be left out"
+        );
+    }
+}
@@ -17,11 +17,17 @@ mod capability;
 mod checker;
 mod commands;
 mod config;
+mod config_check;
 mod crate_name;
+mod date;
 mod demangle;
+mod dot;
+mod lockfile;
 mod print;
+mod report;
 mod reservoir_sample;
 mod rust_path;
+mod sarif;
 mod src_analysis;
 mod symbol;
 mod tree;
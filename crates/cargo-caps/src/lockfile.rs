@@ -0,0 +1,228 @@
+//! `cargo-caps.lock` records the deduced capabilities of every package,
+//! so that we can report only what changed since it was written,
+//! e.g. after a `cargo update`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context as _;
+use cargo_metadata::{TargetKind, camino::Utf8Path, semver::Version};
+use itertools::Itertools as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{CapabilitySet, CrateName, checker::CheckerOutput};
+
+/// The contents of a `cargo-caps.lock` file.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+/// The capabilities of one artifact of a package.
+#[derive(Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: CrateName,
+    pub version: Version,
+    pub target: TargetKind,
+    pub caps: CapabilitySet,
+}
+
+impl Lockfile {
+    pub fn from_output(output: &CheckerOutput) -> Self {
+        let packages = output
+            .checked
+            .iter()
+            .map(|checked| LockedPackage {
+                name: checked.crate_name.clone(),
+                version: checked.version.clone(),
+                target: checked.target_kind.clone(),
+                caps: checked.caps.caps.keys().copied().collect(),
+            })
+            .sorted_by(|a, b| {
+                (&a.name, &a.version, &a.target).cmp(&(&b.name, &b.version, &b.target))
+            })
+            .collect();
+        Self { packages }
+    }
+
+    pub fn from_path(path: &Utf8Path) -> anyhow::Result<Self> {
+        let file = std::fs::read_to_string(path).with_context(|| {
+            format!("Failed to load {path:?} - create it with --update-lockfile")
+        })?;
+        eon::from_str(&file).with_context(|| format!("Failed to deserialize {path:?}"))
+    }
+
+    pub fn save(&self, path: &Utf8Path) -> anyhow::Result<()> {
+        let eon = eon::to_string(self, &Default::default())
+            .with_context(|| format!("Failed to serialize {path:?}"))?;
+        let contents = format!(
+            "// The capabilities of every package, as deduced by cargo-caps.\n\
+             // Generated by `cargo-caps check --update-lockfile` - do not edit by hand.\n\
+             {eon}"
+        );
+        std::fs::write(path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// What capabilities were added or removed in `new`, compared to `self`?
+    ///
+    /// Packages are matched by name and target kind, so that a version bump
+    /// shows up as a change of capabilities rather than as a new package.
+    pub fn diff(&self, new: &Self) -> Vec<CapsChange> {
+        #[derive(Default)]
+        struct Entry {
+            old_versions: BTreeSet<Version>,
+            new_versions: BTreeSet<Version>,
+            old_caps: CapabilitySet,
+            new_caps: CapabilitySet,
+        }
+
+        let mut entries: BTreeMap<(CrateName, TargetKind), Entry> = BTreeMap::new();
+
+        for package in &self.packages {
+            let entry = entries
+                .entry((package.name.clone(), package.target.clone()))
+                .or_default();
+            entry.old_versions.insert(package.version.clone());
+            entry.old_caps.extend(package.caps.iter().copied());
+        }
+        for package in &new.packages {
+            let entry = entries
+                .entry((package.name.clone(), package.target.clone()))
+                .or_default();
+            entry.new_versions.insert(package.version.clone());
+            entry.new_caps.extend(package.caps.iter().copied());
+        }
+
+        entries
+            .into_iter()
+            .filter_map(|((name, target), entry)| {
+                let added: CapabilitySet = entry
+                    .new_caps
+                    .difference(&entry.old_caps)
+                    .copied()
+                    .collect();
+                let removed: CapabilitySet = entry
+                    .old_caps
+                    .difference(&entry.new_caps)
+                    .copied()
+                    .collect();
+                (!added.is_empty() || !removed.is_empty()).then_some(CapsChange {
+                    name,
+                    target,
+                    old_versions: entry.old_versions,
+                    new_versions: entry.new_versions,
+                    added,
+                    removed,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The capabilities of a package changed.
+#[derive(Serialize)]
+pub struct CapsChange {
+    pub name: CrateName,
+    pub target: TargetKind,
+
+    /// Empty if the package is new.
+    pub old_versions: BTreeSet<Version>,
+
+    /// Empty if the package was removed.
+    pub new_versions: BTreeSet<Version>,
+
+    pub added: CapabilitySet,
+    pub removed: CapabilitySet,
+}
+
+impl core::fmt::Display for CapsChange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Self {
+            name,
+            target,
+            old_versions,
+            new_versions,
+            added,
+            removed,
+        } = self;
+
+        write!(f, "{name} ")?;
+        if old_versions.is_empty() {
+            write!(f, "{} (new)", new_versions.iter().join(", "))?;
+        } else if new_versions.is_empty() {
+            write!(f, "{} (removed)", old_versions.iter().join(", "))?;
+        } else if old_versions == new_versions {
+            write!(f, "{}", new_versions.iter().join(", "))?;
+        } else {
+            write!(
+                f,
+                "{} -> {}",
+                old_versions.iter().join(", "),
+                new_versions.iter().join(", ")
+            )?;
+        }
+        write!(f, " ({target}):")?;
+
+        for cap in added {
+            write!(f, " +{}{cap}", cap.emoji())?;
+        }
+        for cap in removed {
+            write!(f, " -{}{cap}", cap.emoji())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Capability;
+
+    use super::*;
+
+    fn package(name: &str, version: &str, caps: &[Capability]) -> LockedPackage {
+        LockedPackage {
+            name: CrateName::new(name).unwrap(),
+            version: Version::parse(version).unwrap(),
+            target: TargetKind::Lib,
+            caps: caps.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Lockfile {
+            packages: vec![
+                package("serde_json", "1.0.1", &[Capability::Alloc]),
+                package("unchanged", "0.1.0", &[Capability::Alloc]),
+                package("removed", "0.1.0", &[Capability::Net]),
+            ],
+        };
+        let new = Lockfile {
+            packages: vec![
+                package("serde_json", "1.0.2", &[Capability::Alloc, Capability::FS]),
+                package("unchanged", "0.2.0", &[Capability::Alloc]),
+                package("added", "0.1.0", &[Capability::Time]),
+            ],
+        };
+
+        let changes = old.diff(&new).iter().map(|c| c.to_string()).collect_vec();
+        assert_eq!(
+            changes,
+            [
+                "added 0.1.0 (new) (lib): +⏰time",
+                "removed 0.1.0 (removed) (lib): -🌐net",
+                "serde_json 1.0.1 -> 1.0.2 (lib): +📁fs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let lockfile = Lockfile {
+            packages: vec![package("serde_json", "1.0.1", &[Capability::Alloc])],
+        };
+        let eon = eon::to_string(&lockfile, &Default::default()).unwrap();
+        let parsed: Lockfile = eon::from_str(&eon).unwrap();
+        assert!(lockfile.diff(&parsed).is_empty());
+        assert_eq!(parsed.packages[0].version.to_string(), "1.0.1");
+    }
+}
@@ -19,8 +19,10 @@ fn main() {
     let result = match args.command {
         Commands::Build(check_cmd) => check_cmd.execute(),
         Commands::Caps(caps_cmd) => caps_cmd.execute(),
+        Commands::Config(config_cmd) => config_cmd.execute(),
         Commands::Init(init_cmd) => init_cmd.execute(),
         Commands::Symbols(symbols_cmd) => symbols_cmd.execute(),
+        Commands::Why(why_cmd) => why_cmd.execute(),
     };
 
     match result {
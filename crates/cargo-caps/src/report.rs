@@ -0,0 +1,353 @@
+//! The machine-readable reports printed by `cargo-caps check --format json`
+//! and `cargo-caps caps --format json`.
+//!
+//! The schema is versioned with [`REPORT_VERSION`].
+//! Adding new fields is fine, but removing or changing the meaning
+//! of an existing field requires bumping the version.
+
+use std::collections::BTreeMap;
+
+use cargo_metadata::{TargetKind, camino::Utf8Path, semver::Version};
+use serde::Serialize;
+
+use crate::{
+    CrateName,
+    build_graph_analysis::{DepKind, Scope},
+    cap_rule::RulePattern,
+    capability::{Capability, CapabilitySet, DeducedCaps, Reason, Reasons},
+    checker::CheckerOutput,
+    config::Denial,
+    lockfile::CapsChange,
+};
+
+/// Bump this whenever the schema changes in a backwards-incompatible way.
+pub const REPORT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Report {
+    /// See [`REPORT_VERSION`].
+    pub version: u32,
+
+    /// Every crate we checked, sorted by name and version.
+    pub crates: Vec<CrateReport>,
+
+    /// Only present when running with `--diff`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockfile_changes: Option<Vec<CapsChange>>,
+}
+
+#[derive(Serialize)]
+pub struct CrateReport {
+    pub name: CrateName,
+    pub version: Version,
+
+    /// One entry for each artifact of the crate, e.g. its library and its `build.rs`.
+    pub targets: Vec<TargetReport>,
+}
+
+#[derive(Serialize)]
+pub struct TargetReport {
+    pub kind: TargetKind,
+
+    /// How the crate is used by the workspace.
+    pub dep_kinds: Vec<DepKind>,
+
+    /// When the code of the artifact runs: `runtime`, `build-time` or `dev`.
+    pub scope: Scope,
+
+    /// The capabilities we deduced, and all the reasons for them.
+    pub capabilities: BTreeMap<Capability, Vec<ReasonReport>>,
+
+    /// The capabilities granted by the workspace config.
+    pub allowed: CapabilitySet,
+
+    /// The capabilities the crate has, but was not granted (or was denied).
+    pub violations: CapabilitySet,
+
+    /// The capabilities the crate has that were forbidden by a deny rule, and by which.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub denials: BTreeMap<Capability, Vec<Denial>>,
+
+    /// The capabilities the crate has beyond what it declares in its own `Cargo.toml`.
+    #[serde(skip_serializing_if = "CapabilitySet::is_empty")]
+    pub undeclared: CapabilitySet,
+}
+
+/// Why a crate has a capability.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReasonReport {
+    /// A path in the source or a symbol matched a rule.
+    PathMatchedRule { path: String, rule: RuleReport },
+
+    /// A linker symbol matched a rule.
+    SymbolMatchedRule {
+        symbol: String,
+        mangled: String,
+        rule: RuleReport,
+    },
+
+    /// We failed to parse the source code.
+    SourceParseError { error: String },
+
+    /// Something we found when analyzing the source code.
+    SourceCode { file: String, line: usize },
+
+    /// A linker symbol that didn't match any rule.
+    UnmatchedSymbol { symbol: String, mangled: String },
+
+    /// A path into `std`, `alloc` or `core` that didn't match any rule.
+    UnmatchedStandardPath { path: String },
+
+    /// We depend on a crate with this capability.
+    Dependency { name: CrateName },
+
+    /// The build script links this native library.
+    LinkedLib { name: String },
+}
+
+/// The rule pattern that matched a path or symbol.
+#[derive(Serialize)]
+pub struct RuleReport {
+    /// Index of the rule in its file.
+    pub index: usize,
+
+    /// The pattern as written in the rule, e.g. `std::net::*`.
+    pub pattern: String,
+
+    /// The file the rule was defined in, e.g. `default_rules.eon`, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl From<&RulePattern> for RuleReport {
+    fn from(rule: &RulePattern) -> Self {
+        Self {
+            index: rule.rule_index,
+            pattern: rule.pattern.to_string(),
+            file: rule
+                .location
+                .as_ref()
+                .map(|location| location.path.to_string()),
+            line: rule.location.as_ref().map(|location| location.line_nr),
+        }
+    }
+}
+
+impl From<&Reason> for ReasonReport {
+    fn from(reason: &Reason) -> Self {
+        match reason {
+            Reason::PathMatchedRule { path, rule } => Self::PathMatchedRule {
+                path: path.to_string(),
+                rule: rule.as_ref().into(),
+            },
+            Reason::SymbolMatchedRule { symbol, rule } => Self::SymbolMatchedRule {
+                symbol: symbol.demangled.clone(),
+                mangled: symbol.mangled.clone(),
+                rule: rule.as_ref().into(),
+            },
+            Reason::SourceParseError(error) => Self::SourceParseError {
+                error: error.clone(),
+            },
+            Reason::SourceCodeAnalysis { location } => Self::SourceCode {
+                file: location.path.to_string(),
+                line: location.line_nr,
+            },
+            Reason::UnmatchedSymbol(symbol) => Self::UnmatchedSymbol {
+                symbol: symbol.demangled.clone(),
+                mangled: symbol.mangled.clone(),
+            },
+            Reason::UmatchedStandardPath(path) => Self::UnmatchedStandardPath {
+                path: path.to_string(),
+            },
+            Reason::Crate(name) => Self::Dependency { name: name.clone() },
+            Reason::LinkedLib(name) => Self::LinkedLib { name: name.clone() },
+        }
+    }
+}
+
+pub fn report_reasons(
+    caps: &BTreeMap<Capability, Reasons>,
+) -> BTreeMap<Capability, Vec<ReasonReport>> {
+    caps.iter()
+        .map(|(&cap, reasons)| (cap, reasons.iter().map(ReasonReport::from).collect()))
+        .collect()
+}
+
+impl Report {
+    pub fn new(output: &CheckerOutput) -> Self {
+        let mut crates: BTreeMap<(CrateName, Version), CrateReport> = BTreeMap::new();
+
+        for checked in &output.checked {
+            let crate_report = crates
+                .entry((checked.crate_name.clone(), checked.version.clone()))
+                .or_insert_with(|| CrateReport {
+                    name: checked.crate_name.clone(),
+                    version: checked.version.clone(),
+                    targets: vec![],
+                });
+
+            crate_report.targets.push(TargetReport {
+                kind: checked.target_kind.clone(),
+                dep_kinds: checked.dep_kinds.kind.iter().copied().collect(),
+                scope: checked.scope(),
+                capabilities: report_reasons(&checked.caps.caps),
+                allowed: checked.allowed.clone(),
+                violations: checked.violations.clone(),
+                denials: checked.denials.clone(),
+                undeclared: checked.undeclared.clone(),
+            });
+        }
+
+        Self {
+            version: REPORT_VERSION,
+            crates: crates.into_values().collect(),
+            lockfile_changes: None,
+        }
+    }
+}
+
+/// The report of `cargo-caps caps --format json`, about a single binary.
+#[derive(Serialize)]
+pub struct BinaryReport {
+    /// See [`REPORT_VERSION`].
+    pub version: u32,
+
+    /// The binary that was analyzed.
+    pub binary: String,
+
+    /// The capabilities we deduced, and all the reasons for them.
+    pub capabilities: BTreeMap<Capability, Vec<ReasonReport>>,
+
+    /// Crates the binary uses, whose capabilities we don't know,
+    /// together with the paths that indicated we use them.
+    pub unresolved_crates: BTreeMap<CrateName, Vec<String>>,
+}
+
+impl BinaryReport {
+    pub fn new(binary: &Utf8Path, caps: &DeducedCaps) -> Self {
+        Self {
+            version: REPORT_VERSION,
+            binary: binary.to_string(),
+            capabilities: report_reasons(&caps.caps),
+            unresolved_crates: caps
+                .unresolved_crates
+                .iter()
+                .map(|(name, paths)| {
+                    (
+                        name.clone(),
+                        paths.iter().map(ToString::to_string).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cap_rule::SymbolRules,
+        checker::CheckedArtifact,
+        rust_path::RustPath,
+        symbol::{Symbol, SymbolKind, SymbolScope},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_report_json() {
+        let mut hyper = CheckedArtifact::test_lib("hyper", &[Capability::Net]);
+        hyper
+            .caps
+            .caps
+            .entry(Capability::Net)
+            .or_default()
+            .insert(Reason::Crate(CrateName::new("socket2").unwrap()));
+        hyper.allowed = core::iter::once(Capability::Alloc).collect();
+        hyper.violations = core::iter::once(Capability::Net).collect();
+        let output = CheckerOutput {
+            checked: vec![hyper],
+            ..Default::default()
+        };
+
+        // Empty `denials`, `undeclared` and `lockfile_changes` are left out.
+        assert_eq!(
+            serde_json::to_value(Report::new(&output)).unwrap(),
+            serde_json::json!({
+                "version": REPORT_VERSION,
+                "crates": [{
+                    "name": "hyper",
+                    "version": "1.0.0",
+                    "targets": [{
+                        "kind": "lib",
+                        "dep_kinds": ["normal"],
+                        "scope": "runtime",
+                        "capabilities": {
+                            "net": [
+                                { "type": "source_code", "file": "hyper/src/lib.rs", "line": 1 },
+                                { "type": "dependency", "name": "socket2" },
+                            ]
+                        },
+                        "allowed": ["alloc"],
+                        "violations": ["net"],
+                    }],
+                }],
+            })
+        );
+    }
+    #[test]
+    fn test_binary_report_json() {
+        let rules = SymbolRules::load_default();
+        let malloc = rules.match_symbol("malloc").unwrap();
+        let malloc_location = malloc.pattern.location.as_ref().unwrap();
+        let reason = Reason::SymbolMatchedRule {
+            symbol: Symbol::with_metadata(
+                "malloc".to_owned(),
+                SymbolScope::Linkage,
+                SymbolKind::Text,
+            ),
+            rule: std::sync::Arc::clone(malloc.pattern),
+        };
+        let caps = DeducedCaps {
+            caps: core::iter::once((Capability::Alloc, core::iter::once(reason).collect()))
+                .collect(),
+            unresolved_crates: core::iter::once((
+                CrateName::new("tokio").unwrap(),
+                core::iter::once(RustPath::new("tokio::spawn")).collect(),
+            ))
+            .collect(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(BinaryReport::new(
+                Utf8Path::new("target/debug/my_app"),
+                &caps
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "version": REPORT_VERSION,
+                "binary": "target/debug/my_app",
+                "capabilities": {
+                    "alloc": [{
+                        "type": "symbol_matched_rule",
+                        "symbol": "malloc",
+                        "mangled": "malloc",
+                        "rule": {
+                            "index": malloc.pattern.rule_index,
+                            "pattern": "malloc",
+                            "file": "default_rules.eon",
+                            "line": malloc_location.line_nr,
+                        },
+                    }]
+                },
+                "unresolved_crates": {
+                    "tokio": ["tokio::spawn"],
+                },
+            })
+        );
+    }
+}
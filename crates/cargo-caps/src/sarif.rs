@@ -0,0 +1,259 @@
+//! [SARIF 2.1](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) output,
+//! for code-scanning tools.
+//!
+//! Each capability a crate has but was not granted becomes an error.
+//! Each capability a crate has beyond its own declaration becomes a warning.
+//! Source locations found by the source analysis become physical locations of that result.
+//! If there are none, we point at the `Cargo.toml` of the crate instead.
+//!
+//! Paths in the workspace are relative to `%SRCROOT%`,
+//! and the sources of registry and git dependencies are relative to `CARGO_HOME`,
+//! so that the report does not depend on the machine it was made on.
+
+use std::collections::BTreeSet;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools as _;
+use serde_json::{Value, json};
+
+use crate::{
+    Capability,
+    capability::Reason,
+    checker::{CheckedArtifact, CheckerOutput, as_relative_path},
+};
+
+/// Locations per result. Code-scanning tools tend to choke on very long lists.
+const MAX_LOCATIONS: usize = 10;
+
+/// The `uriBaseId` of paths in the workspace, i.e. relative to where we were run.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// The `uriBaseId` of paths in the Cargo home, e.g. `~/.cargo`.
+const CARGO_HOME: &str = "CARGO_HOME";
+
+pub fn sarif_report(output: &CheckerOutput) -> Value {
+    let reported_caps: BTreeSet<Capability> = output
+        .checked
+        .iter()
+        .flat_map(|checked| checked.violations.iter().chain(checked.undeclared.iter()))
+        .copied()
+        .collect();
+
+    let rules: Vec<Value> = reported_caps
+        .iter()
+        .map(|cap| {
+            json!({
+                "id": rule_id(*cap),
+                "name": cap.to_string(),
+                "shortDescription": { "text": cap.description() },
+                "defaultConfiguration": { "level": "error" },
+            })
+        })
+        .collect();
+
+    let reported_caps = &reported_caps;
+    let results: Vec<Value> = output
+        .checked
+        .iter()
+        .flat_map(|checked| {
+            let errors = checked
+                .violations
+                .iter()
+                .map(move |&cap| result(reported_caps, checked, cap, "error"));
+            let warnings = checked
+                .undeclared
+                .iter()
+                .filter(|cap| !checked.violations.contains(cap))
+                .map(move |&cap| result(reported_caps, checked, cap, "warning"));
+            errors.chain(warnings)
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-caps",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/emilk/cargo-caps",
+                    "rules": rules,
+                }
+            },
+            "originalUriBaseIds": {
+                SRCROOT: { "description": { "text": "The root of the checked workspace" } },
+                CARGO_HOME: { "description": { "text": "The Cargo home directory, e.g. ~/.cargo" } },
+            },
+            "results": results,
+        }]
+    })
+}
+
+fn rule_id(cap: Capability) -> String {
+    format!("cargo-caps/{cap}")
+}
+
+fn result(
+    reported_caps: &BTreeSet<Capability>,
+    checked: &CheckedArtifact,
+    cap: Capability,
+    level: &str,
+) -> Value {
+    let CheckedArtifact {
+        crate_name,
+        version,
+        target_kind,
+        manifest_path,
+        caps,
+        denials,
+        ..
+    } = checked;
+
+    let mut locations: Vec<Value> = caps
+        .caps
+        .get(&cap)
+        .into_iter()
+        .flatten()
+        .filter_map(|reason| match reason {
+            Reason::SourceCodeAnalysis { location } => {
+                Some(physical_location(&location.path, Some(location.line_nr)))
+            }
+            _ => None,
+        })
+        .take(MAX_LOCATIONS)
+        .collect();
+    if locations.is_empty() {
+        locations.push(physical_location(manifest_path, None));
+    }
+
+    let text = if let Some(denials) = denials.get(&cap) {
+        format!(
+            "{crate_name} {version} ({target_kind}) has the capability `{cap}`, which is forbidden by {}",
+            denials.iter().join(", ")
+        )
+    } else if level == "error" {
+        format!(
+            "{crate_name} {version} ({target_kind}) has the capability `{cap}`, but was not granted it"
+        )
+    } else {
+        format!(
+            "{crate_name} {version} ({target_kind}) has the capability `{cap}`, but does not declare it in its own Cargo.toml"
+        )
+    };
+
+    json!({
+        "ruleId": rule_id(cap),
+        "ruleIndex": reported_caps.iter().position(|&c| c == cap),
+        "level": level,
+        "message": { "text": text },
+        "locations": locations,
+    })
+}
+
+fn physical_location(path: &Utf8Path, line_nr: Option<usize>) -> Value {
+    let mut physical_location = json!({ "artifactLocation": artifact_location(path) });
+    if let Some(line_nr) = line_nr {
+        physical_location["region"] = json!({ "startLine": line_nr });
+    }
+    json!({ "physicalLocation": physical_location })
+}
+
+fn artifact_location(path: &Utf8Path) -> Value {
+    let relative = as_relative_path(path);
+    if relative.is_relative() {
+        json!({ "uri": relative.as_str(), "uriBaseId": SRCROOT })
+    } else if let Some(relative) = cargo_home().and_then(|home| path.strip_prefix(home).ok()) {
+        json!({ "uri": relative.as_str(), "uriBaseId": CARGO_HOME })
+    } else {
+        json!({ "uri": format!("file://{path}") })
+    }
+}
+
+/// Where cargo keeps the sources of registry and git dependencies.
+fn cargo_home() -> Option<Utf8PathBuf> {
+    let path = std::env::var_os("CARGO_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cargo")))?;
+    Utf8PathBuf::from_path_buf(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CrateName;
+
+    use super::*;
+
+    #[test]
+    fn test_sarif_report() {
+        let mut hyper = CheckedArtifact::test_lib("hyper", &[Capability::Net]);
+        hyper.violations = core::iter::once(Capability::Net).collect();
+
+        // Only has `fs` because of a dependency, so there is no source location:
+        let mut tokio = CheckedArtifact::test_lib("tokio", &[]);
+        tokio.caps.caps.insert(
+            Capability::FS,
+            core::iter::once(Reason::Crate(CrateName::new("mio").unwrap())).collect(),
+        );
+        tokio.violations = [Capability::Net, Capability::FS].into_iter().collect();
+        tokio.manifest_path = cargo_home()
+            .unwrap()
+            .join("registry/src/index.crates.io-1949cf8c6b5b557f/tokio-1.47.1/Cargo.toml");
+        tokio.undeclared = [Capability::FS, Capability::Time].into_iter().collect();
+
+        let output = CheckerOutput {
+            checked: vec![hyper, tokio],
+            ..Default::default()
+        };
+        let sarif = sarif_report(&output);
+        let run = &sarif["runs"][0];
+
+        let rule_ids = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap())
+            .collect_vec();
+        assert_eq!(
+            rule_ids,
+            ["cargo-caps/time", "cargo-caps/net", "cargo-caps/fs"]
+        );
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 4);
+        for result in results {
+            let rule_index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(result["ruleId"], rule_ids[rule_index], "{result}");
+        }
+
+        assert_eq!(
+            results[0]["locations"],
+            json!([{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "hyper/src/lib.rs", "uriBaseId": "%SRCROOT%" },
+                    "region": { "startLine": 1 },
+                }
+            }])
+        );
+        assert_eq!(results[2]["ruleId"], "cargo-caps/fs");
+        assert_eq!(
+            results[2]["locations"],
+            json!([{
+                "physicalLocation": {
+                    "artifactLocation": {
+                        "uri": "registry/src/index.crates.io-1949cf8c6b5b557f/tokio-1.47.1/Cargo.toml",
+                        "uriBaseId": "CARGO_HOME",
+                    },
+                }
+            }]),
+            "Without source locations, we should point at the Cargo.toml"
+        );
+        assert!(run["originalUriBaseIds"]["CARGO_HOME"].is_object());
+        assert_eq!(results[2]["level"], "error");
+        assert_eq!(results[3]["ruleId"], "cargo-caps/time");
+        assert_eq!(
+            results[3]["level"], "warning",
+            "Not declaring a capability is not a policy violation"
+        );
+    }
+}
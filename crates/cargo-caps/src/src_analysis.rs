@@ -17,7 +17,7 @@ use std::{
 use anyhow::Context as _;
 use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
 use itertools::Itertools as _;
-use proc_macro2::Span;
+use proc_macro2::{Spacing, Span, TokenStream, TokenTree};
 use syn::{Type, UseTree, punctuated::Punctuated, spanned::Spanned as _, visit::Visit};
 
 use crate::{
@@ -229,6 +229,16 @@ impl ParserState {
 
     /// Process any external path found
     fn process_path(&mut self, syn_path: &syn::Path) {
+        if let Some(full_path) = self.resolve_path(syn_path) {
+            self.all_paths.insert(full_path);
+        }
+    }
+
+    /// Resolve a path using the imports, e.g. `fs::read` to `std::fs::read`.
+    ///
+    /// Returns `None` for single identifiers that don't match an import.
+    /// They are probably referring to locals.
+    fn resolve_path(&self, syn_path: &syn::Path) -> Option<RustPath> {
         let rust_path = as_rust_path(syn_path);
         let segments = rust_path.segments();
 
@@ -242,24 +252,94 @@ impl ParserState {
             .find(|import| import.ident == segments[0])
         {
             // We have a `use X as Y` matching a `Y::…`:
-            let full_path = RustPath::from_segments(
+            Some(RustPath::from_segments(
                 import
                     .path
                     .segments()
                     .iter()
                     .chain(segments.iter().skip(1))
                     .map(|&s| s.to_owned()),
-            );
-            self.all_paths.insert(full_path);
+            ))
         } else if segments.len() < 2 {
-            // Skip single identifiers that doesn't match an import.
-            // They are probably referring to locals.
+            None
         } else {
             // Assume this is already a fully qualified path
-            self.all_paths.insert(rust_path);
+            Some(rust_path)
+        }
+    }
+
+    /// Is this macro invocation inline assembly, e.g. `asm!` or `core::arch::global_asm!`?
+    ///
+    /// To fail safe, an `asm!` we can't resolve is assumed to be the real one.
+    fn is_asm_macro(&self, syn_path: &syn::Path) -> bool {
+        const ASM_MACROS: [&str; 3] = ["asm", "global_asm", "naked_asm"];
+
+        match self.resolve_path(syn_path) {
+            Some(full_path) => {
+                let segments = full_path.segments();
+                matches!(segments.as_slice(), [krate, "arch", name]
+                    if matches!(*krate, "core" | "std") && ASM_MACROS.contains(name))
+            }
+            None => syn_path
+                .get_ident()
+                .is_some_and(|ident| ASM_MACROS.contains(&ident.to_string().as_str())),
+        }
+    }
+
+    /// Find `asm!` and friends in the tokens of other macros, e.g. in `cfg_if! { … }`,
+    /// which syn doesn't parse for us.
+    fn visit_macro_tokens(&mut self, tokens: TokenStream) {
+        let tokens = tokens.into_iter().collect_vec();
+
+        // Is the token at this index preceded by `::`?
+        let after_path_sep = |i: usize| {
+            i >= 2
+                && matches!((&tokens[i - 2], &tokens[i - 1]),
+                    (TokenTree::Punct(a), TokenTree::Punct(b))
+                        if a.as_char() == ':' && a.spacing() == Spacing::Joint && b.as_char() == ':')
+        };
+
+        // Where the path that ends at the current token started, e.g. `core::arch::asm`.
+        let mut path_start = None;
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Ident(_) => {
+                    path_start = if after_path_sep(i) {
+                        path_start.or(Some(i - 2)) // e.g. `::core`
+                    } else {
+                        Some(i)
+                    };
+                }
+                TokenTree::Punct(punct) if punct.as_char() == ':' => {}
+                TokenTree::Punct(punct) if punct.as_char() == '!' => {
+                    if let Some(start) = path_start.take()
+                        && let Ok(path) =
+                            syn::parse2::<syn::Path>(tokens[start..i].iter().cloned().collect())
+                        && self.is_asm_macro(&path)
+                    {
+                        self.add_capability(Capability::Asm, path.span());
+                    }
+                }
+                TokenTree::Group(group) => {
+                    path_start = None;
+                    self.visit_macro_tokens(group.stream());
+                }
+                TokenTree::Punct(_) | TokenTree::Literal(_) => {
+                    path_start = None;
+                }
+            }
         }
     }
 
+    fn add_capability(&mut self, capability: Capability, span: Span) {
+        let location = self.location_from_span(span);
+        self.capabilities
+            .entry(capability)
+            .or_default()
+            .insert(Reason::SourceCodeAnalysis { location });
+    }
+
     fn location_from_span(&self, span: Span) -> SourceLocation {
         SourceLocation {
             path: Arc::clone(&self.current_file),
@@ -312,6 +392,21 @@ impl<'ast> Visit<'ast> for ParserState {
         syn::visit::visit_item_fn(self, item_fn);
     }
 
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if self.is_asm_macro(&mac.path) {
+            self.add_capability(Capability::Asm, mac.span());
+        } else {
+            self.visit_macro_tokens(mac.tokens.clone());
+        }
+        syn::visit::visit_macro(self, mac);
+    }
+
+    fn visit_item_foreign_mod(&mut self, foreign_mod: &'ast syn::ItemForeignMod) {
+        // e.g. `extern "C" { fn foo(); }`
+        self.add_capability(Capability::Ffi, foreign_mod.span());
+        syn::visit::visit_item_foreign_mod(self, foreign_mod);
+    }
+
     fn visit_expr_unsafe(&mut self, expr_unsafe: &'ast syn::ExprUnsafe) {
         // This handles unsafe expressions like `unsafe { ... }`
         let location = self.location_from_span(expr_unsafe.span());
@@ -371,6 +466,11 @@ impl<'ast> Visit<'ast> for ParserState {
     }
 
     fn visit_attribute(&mut self, input: &'ast syn::Attribute) {
+        if input.path().is_ident("link") {
+            // e.g. `#[link(name = "ssl")]`
+            self.add_capability(Capability::Ffi, input.span());
+        }
+
         #[expect(clippy::match_same_arms)]
         match &input.meta {
             syn::Meta::Path(_) => {
@@ -501,4 +601,96 @@ mod tests {
             "Should not detect unsafe in safe code"
         );
     }
+
+    #[test]
+    fn test_ffi_detection() {
+        let content_with_extern_block = r#"
+            unsafe extern "C" {
+                fn strlen(s: *const std::ffi::c_char) -> usize;
+            }
+        "#;
+
+        let content_with_link = r#"
+            #[link(name = "ssl")]
+            unsafe extern "C" {}
+        "#;
+
+        let content_with_extern_fn = r#"
+            pub extern "C" fn callback() {}
+        "#;
+
+        let ffi_lines = |content: &str| {
+            ParsedRust::parse_content(content)
+                .unwrap()
+                .capabilities
+                .get(&Capability::Ffi)
+                .into_iter()
+                .flatten()
+                .map(|reason| match reason {
+                    Reason::SourceCodeAnalysis { location } => location.line_nr,
+                    _ => panic!("Unexpected reason: {reason}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ffi_lines(content_with_extern_block), [2]);
+        assert_eq!(
+            ffi_lines(content_with_link),
+            [2],
+            "The #[link] attribute is part of the extern block"
+        );
+        assert!(
+            ffi_lines(content_with_extern_fn).is_empty(),
+            "Defining a function with the C ABI does not call any foreign code"
+        );
+    }
+
+    #[test]
+    fn test_asm_detection() {
+        let content = r#"
+            use core::arch::asm;
+            use std::arch::global_asm as my_global_asm;
+
+            fn main() {
+                unsafe { asm!("nop") };
+                unsafe { core::arch::asm!("nop") };
+                println!("Not assembly");
+                my_crate::asm!("Not assembly either");
+            }
+
+            my_global_asm!("nop");
+
+            #[unsafe(naked)]
+            extern "C" fn naked() {
+                std::arch::naked_asm!("ret")
+            }
+
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "x86_64")] {
+                    fn nop() { unsafe { ::core::arch::asm!("nop") } }
+                }
+            }
+
+            macro_rules! my_nop {
+                () => { asm!("nop") };
+                ($x:expr) => { my_crate::asm!($x) };
+            }
+        "#;
+
+        let parsed = ParsedRust::parse_content(content).unwrap();
+        let asm_lines = parsed.capabilities[&Capability::Asm]
+            .iter()
+            .map(|reason| match reason {
+                Reason::SourceCodeAnalysis { location } => location.line_nr,
+                _ => panic!("Unexpected reason: {reason}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(asm_lines, [6, 7, 12, 16, 21, 26]);
+
+        let unresolved = ParsedRust::parse_content(r#"fn f() { unsafe { asm!("nop") } }"#).unwrap();
+        assert!(
+            unresolved.capabilities.contains_key(&Capability::Asm),
+            "An asm! we can't resolve should be assumed to be inline assembly"
+        );
+    }
 }
@@ -87,7 +87,10 @@ impl fmt::Display for FunctionOrPath {
 
 impl FunctionOrPath {
     pub fn from_demangled(demangled: &str) -> Vec<Self> {
-        if demangled.starts_with("rustc[") {
+        if let Some(fun_name) = demangled.strip_prefix("rustc::") {
+            // Example: 'rustc::__rust_alloc' (v0 symbol)
+            vec![Self::Function(fun_name.to_owned())]
+        } else if demangled.starts_with("rustc[") {
             // Example: 'rustc[5224e6b81cd82a8f]::__rust_alloc'
             // Get part after `]::`:
             if let Some(end_bracket) = demangled.find("]::") {
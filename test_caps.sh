@@ -7,6 +7,7 @@
 #   ./test_caps.sh -F alloc,time
 #   ./test_caps.sh -F all --verbose
 #   ./test_caps.sh -F fread,fwrite
+#   ./test_caps.sh -F fs
 #
 
 # TODO: turn this into a cargo test that turns on each feature in turn and checks that `cargo-caps` detects the correct feature set.